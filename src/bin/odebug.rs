@@ -0,0 +1,312 @@
+//! Command line tool for working with the logs in the odebug debug directory.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use odebug::{parse, report, Entry, Format};
+use regex::Regex;
+
+const USAGE: &str = "\
+Usage: odebug <command> [options]
+
+Commands:
+  dir                       Print the resolved debug directory
+  list                      List log files with their sizes and entry counts
+  tail [-f] [-n N] <file>   Print the last N entries of a file (default 10),
+                            with -f keep printing entries as they are written
+  filter [options] [file]   Print the entries matching all given options,
+                            from every log file if no file is given
+      --header <text>       Header contains text
+      --source <text>       Source context (file:line) contains text
+      --level <level>       Header starts with the level, e.g. DEBUG
+      --regex <pattern>     Header or content matches the pattern
+      --field <key=value>   Field key is present with the value, or
+                            with any value if only <key> is given
+  report [-o <file>]        Write an HTML report of all logs, by default to
+                            report.html in the debug directory
+  clean                     Remove all files and subdirectories from the
+                            debug directory
+  help                      Print this message
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("dir") => {
+            println!("{}", debug_dir().display());
+            Ok(())
+        },
+        Some("list") => list(),
+        Some("tail") => tail(&args[1..]),
+        Some("filter") => filter(&args[1..]),
+        Some("report") => report(&args[1..]),
+        Some("clean") => clean(),
+        Some("help" | "-h" | "--help") => {
+            print!("{}", USAGE);
+            Ok(())
+        },
+        Some(other) => Err(format!("unknown command `{}`\n\n{}", other, USAGE)),
+        None => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message.trim_end());
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// The debug directory of the workspace. Reading logs must not create it,
+/// nor pick a fallback other than the one the build wrote to, so this uses
+/// `find_location`, without the overrides `debug_dir` also checks.
+fn debug_dir() -> PathBuf {
+    // never `Location::Stderr`
+    let location = odebug::find_location();
+    location.dir().map(Path::to_path_buf).unwrap_or_default()
+}
+
+fn log_files() -> Result<Vec<PathBuf>, String> {
+    let dir = debug_dir();
+    parse::log_files(&dir).map_err(|e| format!("{}: {}", dir.display(), e))
+}
+
+fn resolve_file(name: &str) -> PathBuf {
+    let path = Path::new(name);
+    if path.exists() {
+        path.to_path_buf()
+    } else {
+        debug_dir().join(name)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Entry>, String> {
+    parse::read_file(path)
+        .and_then(|entries| entries.collect())
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn list() -> Result<(), String> {
+    let files = log_files()?;
+    let dir = debug_dir();
+    println!("{}", dir.display());
+    for path in files {
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+        let entries = read(&path).map(|entries| entries.len()).unwrap_or_default();
+        println!(
+            "  {:<32} {:>10}  {:>6} entries",
+            path.strip_prefix(&dir).unwrap_or(&path).display(),
+            human_size(size),
+            entries
+        );
+    }
+    Ok(())
+}
+
+fn human_size(size: u64) -> String {
+    match size {
+        0..=1023 => format!("{} B", size),
+        1024..=1_048_575 => format!("{:.1} KiB", size as f64 / 1024.0),
+        _ => format!("{:.1} MiB", size as f64 / 1_048_576.0),
+    }
+}
+
+fn tail(args: &[String]) -> Result<(), String> {
+    let mut follow = false;
+    let mut count = 10;
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--follow" => follow = true,
+            "-n" => {
+                count = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("-n expects a number")?;
+            },
+            option if option.starts_with('-') => return Err(unknown_option(option)),
+            name => file = Some(resolve_file(name)),
+        }
+    }
+    let path = file.ok_or("tail expects a file")?;
+
+    let len = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+    let entries = read(&path)?;
+    let mut stdout = io::stdout().lock();
+    for entry in &entries[entries.len().saturating_sub(count)..] {
+        let _ = Format::Text.write_entry(entry, &mut stdout);
+    }
+    let _ = stdout.flush();
+
+    if follow {
+        follow_file(&path, len)?;
+    }
+    Ok(())
+}
+
+/// Prints the entries appended to `path` after `position` as they are
+/// written, in the layout `tail` prints the last entries in.
+fn follow_file(path: &Path, mut position: u64) -> Result<(), String> {
+    // the extension selects how the appended lines are parsed
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut buf = Vec::new();
+    loop {
+        thread::sleep(Duration::from_millis(250));
+        let Ok(mut file) = File::open(path) else {
+            // the file was removed, wait for it to be recreated
+            position = 0;
+            buf.clear();
+            continue;
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+        if len < position {
+            // truncated by a new run
+            position = 0;
+            buf.clear();
+        }
+        if len == position {
+            continue;
+        }
+
+        let read = file
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| file.read_to_end(&mut buf))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        position += read as u64;
+
+        // a line still being written is parsed in the next round
+        let Some(end) = buf.iter().rposition(|&byte| byte == b'\n') else {
+            continue;
+        };
+        let complete: Vec<u8> = buf.drain(..=end).collect();
+        let mut stdout = io::stdout().lock();
+        for entry in parse::entries(complete.as_slice(), name.as_str()).filter_map(Result::ok) {
+            let _ = Format::Text.write_entry(&entry, &mut stdout);
+        }
+        let _ = stdout.flush();
+    }
+}
+
+fn unknown_option(option: &str) -> String {
+    format!("unknown option `{}`\n\n{}", option, USAGE)
+}
+
+#[derive(Default)]
+struct Filter {
+    header: Option<String>,
+    source: Option<String>,
+    level: Option<String>,
+    regex: Option<Regex>,
+    fields: Vec<(String, Option<String>)>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        let header = entry.header.as_deref().unwrap_or_default();
+        let context = entry.context.as_deref().unwrap_or_default();
+        self.header
+            .as_ref()
+            .map_or(true, |h| header.contains(h.as_str()))
+            && self
+                .source
+                .as_ref()
+                .map_or(true, |s| context.contains(s.as_str()))
+            && self.level.as_ref().map_or(true, |level| {
+                header
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|first| first.eq_ignore_ascii_case(level))
+            })
+            && self.regex.as_ref().map_or(true, |re| {
+                re.is_match(header) || re.is_match(&entry.content)
+            })
+            && self.fields.iter().all(|(key, expected)| {
+                entry.field(key).is_some_and(|value| {
+                    expected
+                        .as_ref()
+                        .map_or(true, |expected| value.to_string() == *expected)
+                })
+            })
+    }
+}
+
+fn filter(args: &[String]) -> Result<(), String> {
+    let mut filter = Filter::default();
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} expects a value", arg))
+        };
+        match arg.as_str() {
+            "--header" => filter.header = Some(value()?),
+            "--source" => filter.source = Some(value()?),
+            "--level" => filter.level = Some(value()?),
+            "--regex" => {
+                let pattern = value()?;
+                let regex = Regex::new(&pattern).map_err(|e| e.to_string())?;
+                filter.regex = Some(regex);
+            },
+            "--field" => {
+                let field = value()?;
+                filter.fields.push(match field.split_once('=') {
+                    Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                    None => (field, None),
+                });
+            },
+            option if option.starts_with('-') => return Err(unknown_option(option)),
+            name => file = Some(resolve_file(name)),
+        }
+    }
+
+    let files = match file {
+        Some(path) => vec![path],
+        None => log_files()?,
+    };
+    let mut stdout = io::stdout().lock();
+    for path in files {
+        for entry in read(&path)?.iter().filter(|entry| filter.matches(entry)) {
+            let _ = Format::Text.write_entry(entry, &mut stdout);
+        }
+    }
+    Ok(())
+}
+
+fn report(args: &[String]) -> Result<(), String> {
+    let output = match args {
+        [] => debug_dir().join(report::REPORT_FILE),
+        [flag, path] if flag == "-o" || flag == "--output" => PathBuf::from(path),
+        _ => return Err("report expects at most `-o <file>`".to_string()),
+    };
+    report::generate_html_report(&output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+fn clean() -> Result<(), String> {
+    let dir = debug_dir();
+    let read_dir = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut removed = 0;
+    for entry in read_dir.filter_map(Result::ok) {
+        let path = entry.path();
+        let result = match path.is_dir() {
+            true => fs::remove_dir_all(&path),
+            false => fs::remove_file(&path),
+        };
+        result.map_err(|e| format!("{}: {}", path.display(), e))?;
+        removed += 1;
+    }
+    println!("Removed {} entries from {}", removed, dir.display());
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use crate::entry::Entry;
+use crate::exit;
+
+/// The file the summary of suppressed entries is written to at exit.
+pub const SUPPRESSED_FILE: &str = "debug.log";
+
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+static SUPPRESSING: Lazy<Mutex<Vec<&'static CallSite>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// State of a single rate-limited macro call site, kept in a static
+/// generated by [`odebug_once!`](crate::odebug_once),
+/// [`odebug_every!`](crate::odebug_every) and
+/// [`odebug_throttle!`](crate::odebug_throttle).
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct CallSite {
+    location: &'static str,
+    hits: AtomicU64,
+    /// Nanoseconds since `START` of the last logged hit, plus one so that
+    /// zero means never
+    last: AtomicU64,
+    suppressed: AtomicU64,
+    registered: AtomicBool,
+}
+
+impl CallSite {
+    pub const fn new(location: &'static str) -> Self {
+        CallSite {
+            location,
+            hits: AtomicU64::new(0),
+            last: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns `true` for the first hit only.
+    pub fn once(&'static self) -> bool {
+        self.every(u64::MAX)
+    }
+
+    /// Returns `true` for the first hit and every `n`th one after it.
+    pub fn every(&'static self, n: u64) -> bool {
+        let hit = self.hits.fetch_add(1, Ordering::Relaxed);
+        if hit % n.max(1) == 0 {
+            true
+        } else {
+            self.suppress()
+        }
+    }
+
+    /// Returns `true` if no hit was let through within the last `period`.
+    pub fn throttle(&'static self, period: Duration) -> bool {
+        let now = START.elapsed().as_nanos() as u64 + 1;
+        let last = self.last.load(Ordering::Relaxed);
+        if last != 0 && now.saturating_sub(last) < period.as_nanos() as u64 {
+            return self.suppress();
+        }
+        match self
+            .last
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => true,
+            // another thread got there first
+            Err(_) => self.suppress(),
+        }
+    }
+
+    fn suppress(&'static self) -> bool {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        if !self.registered.swap(true, Ordering::Relaxed) {
+            if let Ok(mut sites) = SUPPRESSING.lock() {
+                if sites.is_empty() {
+                    exit::on_exit(log_summary);
+                }
+                sites.push(self);
+            }
+        }
+        false
+    }
+}
+
+/// Returns the call sites that suppressed entries so far, with their
+/// counts, e.g. `("src/lib.rs:42:9", 17)`.
+pub fn suppressed() -> Vec<(&'static str, u64)> {
+    let sites = match SUPPRESSING.lock() {
+        Ok(sites) => sites,
+        Err(_) => return Vec::new(),
+    };
+    sites
+        .iter()
+        .map(|site| (site.location, site.suppressed.load(Ordering::Relaxed)))
+        .collect()
+}
+
+fn summary() -> Option<String> {
+    let sites = suppressed();
+    let width = sites.iter().map(|(location, _)| location.len()).max()?;
+    let mut content = String::new();
+    for (location, count) in sites {
+        let _ = writeln!(content, "{:<width$}  {} suppressed", location, count);
+    }
+    content.pop();
+    Some(content)
+}
+
+fn log_summary() {
+    if let Some(content) = summary() {
+        let entry = Entry::new(SUPPRESSED_FILE, content).with_header("SUPPRESSED");
+        let logger = crate::logger();
+        let _ = logger.log_entry(&entry);
+        let _ = logger.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_once() {
+        static SITE: CallSite = CallSite::new("once.rs:1");
+        let hits: Vec<bool> = (0..4).map(|_| SITE.once()).collect();
+        assert_eq!(hits, [true, false, false, false]);
+        assert!(suppressed().contains(&("once.rs:1", 3)));
+    }
+
+    #[test]
+    fn test_every() {
+        static SITE: CallSite = CallSite::new("every.rs:1");
+        let hits: Vec<bool> = (0..7).map(|_| SITE.every(3)).collect();
+        assert_eq!(hits, [true, false, false, true, false, false, true]);
+        assert!(suppressed().contains(&("every.rs:1", 4)));
+        let summary = summary().unwrap();
+        assert!(
+            summary
+                .lines()
+                .any(|line| line.starts_with("every.rs:1 ") && line.ends_with(" 4 suppressed")),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn test_throttle() {
+        static SITE: CallSite = CallSite::new("throttle.rs:1");
+        assert!(SITE.throttle(Duration::from_secs(3600)));
+        assert!(!SITE.throttle(Duration::from_secs(3600)));
+        assert!(SITE.throttle(Duration::ZERO));
+    }
+}
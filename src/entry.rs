@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::format::Format;
+
+/// What kind of content an [`Entry`] holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentKind {
+    /// Plain text
+    #[default]
+    Text,
+    /// Rust source, such as a token stream logged with
+    /// [`odebug_tokens!`](crate::odebug_tokens)
+    Rust,
+}
+
+/// The value of a structured field of an [`Entry`].
+///
+/// Fields logged with `odebug!("content"; key = value)` keep the type of
+/// `value` if it is a number, a `bool` or a string. Values logged with the
+/// `key = %value` or `key = ?value` sigils are stored as the string of their
+/// `Display` or `Debug` formatting.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// A boolean
+    Bool(bool),
+    /// A signed integer
+    Int(i64),
+    /// An unsigned integer too large for [`Value::Int`]
+    UInt(u64),
+    /// A floating point number
+    Float(f64),
+    /// A string, or anything logged with `%` or `?`
+    Str(String),
+}
+
+impl Value {
+    /// Reads a value back from its `Display` formatting, choosing the
+    /// first type that fits: `bool`, `i64`, `u64`, `f64` and otherwise
+    /// string.
+    pub fn infer(text: &str) -> Self {
+        if let Ok(value) = text.parse() {
+            Value::Bool(value)
+        } else if let Ok(value) = text.parse() {
+            Value::Int(value)
+        } else if let Ok(value) = text.parse() {
+            Value::UInt(value)
+        } else if let Some(value) = text
+            .parse()
+            .ok()
+            .filter(|_| text.bytes().any(|b| b.is_ascii_digit()))
+        {
+            Value::Float(value)
+        } else {
+            Value::Str(text.to_string())
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::UInt(a), Value::UInt(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Str(a), Value::Str(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => value.fmt(f),
+            Value::Int(value) => value.fmt(f),
+            Value::UInt(value) => value.fmt(f),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Str(value) => value.fmt(f),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident: $($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Value::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(Bool: bool);
+impl_from!(Int: i8, i16, i32, i64, u8, u16, u32);
+impl_from!(Float: f32, f64);
+impl_from!(Str: char, &str, String, &String);
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        match i64::try_from(value) {
+            Ok(value) => Value::Int(value),
+            Err(_) => Value::UInt(value),
+        }
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::from(value as u64)
+    }
+}
+
+impl From<isize> for Value {
+    fn from(value: isize) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+/// A single log entry as it travels from a macro call to the sinks.
+///
+/// The macros and [`write_to_debug_file`](crate::write_to_debug_file) build
+/// one of these for every call; custom sinks and filters receive it by
+/// reference.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Entry {
+    /// Name of the log file the entry is destined for, e.g. `debug.log`
+    pub file: String,
+    /// Optional header shown above the content
+    pub header: Option<String>,
+    /// Optional context, typically `file:line` of the call site
+    pub context: Option<String>,
+    /// The logged content itself
+    pub content: String,
+    /// What kind of content the entry holds
+    pub kind: ContentKind,
+    /// Layout for this entry only, overriding the formatter of its file,
+    /// e.g. [`Format::Compact`] for `odebug!(compact; ...)`. It also picks
+    /// the extension, so a JSON entry goes to `.jsonl` under a text logger.
+    pub format: Option<Format>,
+    /// Structured key-value fields, in the order they were logged
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Entry {
+    /// Creates a new entry for `file` with the given content and no header
+    /// or context.
+    pub fn new(file: impl Into<String>, content: impl Into<String>) -> Self {
+        Entry {
+            file: file.into(),
+            header: None,
+            context: None,
+            content: content.into(),
+            kind: ContentKind::default(),
+            format: None,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Sets the header of the entry.
+    pub fn with_header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    /// Sets the context of the entry.
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    /// Sets the content kind of the entry.
+    pub fn with_kind(mut self, kind: ContentKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Adds a structured field.
+    pub fn with_field(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds structured fields, as produced by the `; key = value` part of
+    /// the macros.
+    pub fn with_fields(mut self, fields: Vec<(String, Value)>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
+
+    /// Returns the value of the field `key`, if present.
+    pub fn field(&self, key: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Renders the entry in `format`, whatever formatter its file uses, and
+    /// writes it to a file with the extension of `format`.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+
+/// Maps a `log`/`tracing` style target such as `my_crate::parser` to a log
+/// file name, `my_crate.parser.log`.
+#[cfg_attr(not(any(feature = "log", feature = "tracing")), allow(dead_code))]
+pub(crate) fn target_file(target: &str) -> String {
+    format!("{}.log", target.replace("::", "."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_conversions() {
+        assert_eq!(Value::from(3usize), Value::Int(3));
+        assert_eq!(Value::from(u64::MAX), Value::UInt(u64::MAX));
+        assert_eq!(Value::from("Foo"), Value::Str("Foo".to_string()));
+        assert_eq!(Value::from(1.5f32), Value::Float(1.5));
+    }
+
+    #[test]
+    fn test_value_infer_roundtrip() {
+        for value in [
+            Value::Bool(true),
+            Value::Int(-3),
+            Value::UInt(u64::MAX),
+            Value::Float(0.25),
+            Value::Str("Foo".to_string()),
+            Value::Str("inf".to_string()),
+        ] {
+            assert_eq!(Value::infer(&value.to_string()), value);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::exit;
+
+/// A failure to log an entry.
+///
+/// [`odebug!`](crate::odebug) and the other logging macros never return
+/// failures. They are handled according to the [`ErrorPolicy`] of the
+/// logger, and the most recent one is kept for [`last_error`] and
+/// [`health`]. [`try_odebug!`](crate::try_odebug) returns them instead.
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    operation: Option<Operation>,
+    path: Option<PathBuf>,
+    source: Option<Arc<io::Error>>,
+}
+
+/// What went wrong, see [`Error::kind`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The debug directory, or a subdirectory of it, could not be created
+    Directory,
+    /// A log file or directory could not be accessed due to missing
+    /// permissions, or would be outside of the debug directory
+    Permission,
+    /// The log file name was rejected, see
+    /// [`validate_file_name`](crate::validate_file_name)
+    InvalidName,
+    /// A [`Formatter`](crate::Formatter) failed to render the entry
+    Format,
+    /// Any other I/O failure, e.g. of a sink
+    Io,
+}
+
+/// What odebug was doing when an [`Error`] happened, see
+/// [`Error::operation`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Checking the log file name
+    Validate,
+    /// Creating the debug directory, or a subdirectory of it
+    CreateDirectory,
+    /// Rendering the entry with a [`Formatter`](crate::Formatter)
+    Format,
+    /// Opening the log file
+    Open,
+    /// Writing to the log file, or to another sink
+    Write,
+    /// Flushing a sink
+    Flush,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Validate => "validate",
+            Operation::CreateDirectory => "create directory",
+            Operation::Format => "format",
+            Operation::Open => "open",
+            Operation::Write => "write",
+            Operation::Flush => "flush",
+        })
+    }
+}
+
+impl Error {
+    /// Creates an error of the given kind.
+    pub fn new(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            operation: None,
+            path: None,
+            source: None,
+        }
+    }
+
+    /// Sets the operation that failed.
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    /// Classifies the error of [`validate_file_name`](crate::validate_file_name)
+    /// rejecting a file name.
+    pub(crate) fn invalid_name(source: io::Error) -> Self {
+        Error::new(ErrorKind::InvalidName)
+            .with_operation(Operation::Validate)
+            .with_source(source)
+    }
+
+    /// Sets the operation that failed, unless it is known already.
+    pub(crate) fn or_operation(mut self, operation: Operation) -> Self {
+        self.operation.get_or_insert(operation);
+        self
+    }
+
+    /// Sets the path of the file or directory the error is about.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Sets the underlying I/O error.
+    pub fn with_source(mut self, source: io::Error) -> Self {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    /// Returns what went wrong.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the operation that failed, if known.
+    pub fn operation(&self) -> Option<Operation> {
+        self.operation
+    }
+
+    /// Returns the file or directory the error is about, if known. For
+    /// files written by the file output this is the resolved path inside
+    /// of the debug directory.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the underlying I/O error, if any.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.source.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, &self.source) {
+            // the source already names the file and the problem
+            (ErrorKind::InvalidName, Some(source)) => return write!(f, "{}", source),
+            (ErrorKind::Directory, _) => f.write_str("cannot create debug directory")?,
+            (ErrorKind::Permission, _) => f.write_str("permission denied")?,
+            (ErrorKind::InvalidName, None) => f.write_str("invalid log file name")?,
+            (ErrorKind::Format, _) => f.write_str("failed to format entry")?,
+            (ErrorKind::Io, _) => match self.operation {
+                Some(operation) => write!(f, "failed to {}", operation)?,
+                None => f.write_str("failed to write debug log")?,
+            },
+        }
+        if let Some(path) = &self.path {
+            write!(f, " {}", path.display())?;
+        }
+        match &self.source {
+            Some(source) => write!(f, ": {}", source),
+            None => Ok(()),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn StdError + 'static))
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Classifies an I/O error by its kind. Errors created from an
+    /// [`Error`], as sinks do, are unwrapped again. Only file names rejected
+    /// by odebug itself are [`ErrorKind::InvalidName`], other invalid input
+    /// is an [`ErrorKind::Io`] error.
+    fn from(error: io::Error) -> Self {
+        if let Some(inner) = error.get_ref().and_then(|e| e.downcast_ref::<Error>()) {
+            return inner.clone();
+        }
+        let kind = match error.kind() {
+            io::ErrorKind::PermissionDenied => ErrorKind::Permission,
+            _ => ErrorKind::Io,
+        };
+        Error::new(kind).with_source(error)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        let kind = match (error.kind, &error.source) {
+            (ErrorKind::Permission, _) => io::ErrorKind::PermissionDenied,
+            (ErrorKind::InvalidName, _) => io::ErrorKind::InvalidInput,
+            (_, Some(source)) => source.kind(),
+            (_, None) => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// What happens when logging from the macros fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Do nothing besides recording the error for [`health`]
+    Ignore,
+    /// Print the first error to stderr, and ignore later ones
+    #[default]
+    EprintOnce,
+    /// Panic, to make broken logging impossible to miss in tests
+    Panic,
+    /// Count the errors and print a summary to stderr when the process
+    /// exits
+    CountAndReport,
+}
+
+impl ErrorPolicy {
+    /// Handles a failure that was already recorded.
+    pub(crate) fn handle(self, error: Error) {
+        match self {
+            ErrorPolicy::Ignore => {},
+            ErrorPolicy::EprintOnce => {
+                if !PRINTED.swap(true, Ordering::Relaxed) {
+                    eprintln!("odebug: {} (further errors are not shown)", error);
+                }
+            },
+            ErrorPolicy::Panic => panic!("odebug: {}", error),
+            ErrorPolicy::CountAndReport => {
+                if !REPORTING.swap(true, Ordering::Relaxed) {
+                    exit::on_exit(report);
+                }
+            },
+        }
+    }
+}
+
+/// The logging health of the process, see [`health`].
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    /// Number of failures so far
+    pub errors: u64,
+    /// The most recent failure
+    pub last_error: Option<Error>,
+}
+
+impl Health {
+    /// Returns `true` if nothing failed so far.
+    pub fn is_ok(&self) -> bool {
+        self.errors == 0
+    }
+}
+
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+static LAST_ERROR: Mutex<Option<Error>> = Mutex::new(None);
+static PRINTED: AtomicBool = AtomicBool::new(false);
+static REPORTING: AtomicBool = AtomicBool::new(false);
+
+/// Records a failure for [`health`] and [`last_error`].
+pub(crate) fn record(error: &Error) {
+    ERRORS.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut last) = LAST_ERROR.lock() {
+        *last = Some(error.clone());
+    }
+}
+
+/// Returns the most recent logging failure of the process, if any.
+pub fn last_error() -> Option<Error> {
+    LAST_ERROR.lock().ok().and_then(|last| last.clone())
+}
+
+/// Returns how many logging failures happened so far, and the last one.
+///
+/// # Examples
+///
+/// ```
+/// let health = odebug::health();
+/// if !health.is_ok() {
+///     eprintln!("{} log entries were lost", health.errors);
+/// }
+/// ```
+pub fn health() -> Health {
+    Health {
+        errors: ERRORS.load(Ordering::Relaxed),
+        last_error: last_error(),
+    }
+}
+
+fn report() {
+    let health = health();
+    if let Some(error) = health.last_error {
+        eprintln!(
+            "odebug: {} errors while logging, the last one: {}",
+            health.errors, error
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_roundtrip_keeps_classification() {
+        let error = Error::new(ErrorKind::Directory)
+            .with_path("/nowhere")
+            .with_source(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        let io_error = io::Error::from(error);
+        assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+
+        let error = Error::from(io_error);
+        assert_eq!(error.kind(), ErrorKind::Directory);
+        assert_eq!(error.path(), Some(Path::new("/nowhere")));
+        assert_eq!(
+            error.to_string(),
+            "cannot create debug directory /nowhere: missing"
+        );
+    }
+
+    #[test]
+    fn test_classification() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        assert_eq!(Error::from(denied).kind(), ErrorKind::Permission);
+        let other = io::Error::new(io::ErrorKind::Other, "broken pipe");
+        assert_eq!(Error::from(other).kind(), ErrorKind::Io);
+        let invalid = io::Error::new(io::ErrorKind::InvalidInput, "bad seek");
+        assert_eq!(Error::from(invalid).kind(), ErrorKind::Io);
+    }
+
+    #[test]
+    fn test_invalid_name_roundtrip() {
+        let rejected = io::Error::new(io::ErrorKind::InvalidInput, "empty file name");
+        let io_error = io::Error::from(Error::invalid_name(rejected));
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidInput);
+        let error = Error::from(io_error);
+        assert_eq!(error.kind(), ErrorKind::InvalidName);
+        assert_eq!(error.operation(), Some(Operation::Validate));
+
+        // invalid input a sink ran into is not about the name
+        let invalid = Error::from(io::Error::new(io::ErrorKind::InvalidInput, "bad seek"));
+        let io_error = io::Error::from(invalid);
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(Error::from(io_error).kind(), ErrorKind::Io);
+    }
+
+    #[test]
+    fn test_errors_are_recorded() {
+        let before = health().errors;
+        let error = Error::new(ErrorKind::Format).with_path("x.log");
+        record(&error);
+        ErrorPolicy::Ignore.handle(error);
+        assert!(health().errors > before);
+        assert!(last_error().is_some());
+    }
+
+    #[test]
+    fn test_display_names_operation() {
+        let error = Error::from(io::Error::new(io::ErrorKind::Other, "disk full"))
+            .with_operation(Operation::Write)
+            .with_path("/debug/expand.log");
+        assert_eq!(
+            error.to_string(),
+            "failed to write /debug/expand.log: disk full"
+        );
+        let error = error.or_operation(Operation::Flush);
+        assert_eq!(error.operation(), Some(Operation::Write));
+    }
+}
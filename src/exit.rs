@@ -0,0 +1,43 @@
+//! Hooks run when the process exits.
+
+use std::os::raw::c_int;
+use std::sync::{Mutex, Once};
+
+use once_cell::sync::Lazy;
+
+// the explicit ABI would be stripped by `force_explicit_abi = false`
+#[rustfmt::skip]
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> c_int;
+}
+
+type Hook = fn();
+
+static HOOKS: Lazy<Mutex<Vec<Hook>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static REGISTER: Once = Once::new();
+
+/// Runs `hook` when the process exits normally, after `main` returns or
+/// on `std::process::exit`. Hooks run in the order they were added.
+pub(crate) fn on_exit(hook: fn()) {
+    if let Ok(mut hooks) = HOOKS.lock() {
+        hooks.push(hook);
+    }
+    REGISTER.call_once(|| {
+        // SAFETY: `run_hooks` is a plain function that never unwinds
+        unsafe {
+            atexit(run_hooks);
+        }
+    });
+}
+
+#[rustfmt::skip]
+extern "C" fn run_hooks() {
+    let hooks = match HOOKS.lock() {
+        Ok(mut hooks) => std::mem::take(&mut *hooks),
+        Err(_) => return,
+    };
+    for hook in hooks {
+        // a panic must not unwind into the C runtime
+        let _ = std::panic::catch_unwind(hook);
+    }
+}
@@ -0,0 +1,502 @@
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+
+use crate::entry::{ContentKind, Entry, Value};
+use crate::SEPARATOR_LINE;
+
+/// Prefix of the field lines following the title line in [`Format::Text`]
+/// and [`Format::Framed`].
+pub(crate) const FIELD_PREFIX: &str = ">   ";
+
+/// Prefix of the fence lines delimiting entries in [`Format::Framed`].
+pub(crate) const FENCE_PREFIX: &str = "~~~odebug-";
+
+static SESSION_FENCE: Lazy<String> = Lazy::new(|| {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let id = RandomState::new().hash_one((nanos, std::process::id()));
+    format!("{}{:016x}", FENCE_PREFIX, id)
+});
+
+/// Returns the fence used by [`Format::Framed`] in this process, e.g.
+/// `~~~odebug-3f9a2c1b7e4d8a60`.
+pub fn session_fence() -> &'static str {
+    &SESSION_FENCE
+}
+
+/// Returns `true` if `line` is a fence written by [`Format::Framed`].
+pub(crate) fn is_fence(line: &str) -> bool {
+    line.strip_prefix(FENCE_PREFIX)
+        .is_some_and(|id| id.len() == 16 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Renders entries before they are handed to the sinks.
+///
+/// The built-in layouts are the variants of [`Format`]. Custom formatters
+/// can be registered for all files with
+/// [`LoggerBuilder::formatter`](crate::LoggerBuilder::formatter) or for a
+/// single file with
+/// [`LoggerBuilder::file_formatter`](crate::LoggerBuilder::file_formatter).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Write};
+///
+/// use odebug::{Entry, Formatter, Logger};
+///
+/// struct ContentOnly;
+///
+/// impl Formatter for ContentOnly {
+///     fn format(&self, entry: &Entry, out: &mut dyn Write) -> io::Result<()> {
+///         writeln!(out, "{}", entry.content)
+///     }
+/// }
+///
+/// let logger = Logger::builder()
+///     .file_formatter("tokens.log", ContentOnly)
+///     .build();
+/// ```
+pub trait Formatter: Send + Sync {
+    /// Writes `entry` to `out`.
+    fn format(&self, entry: &Entry, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Returns the name of the file an entry for `file` is written to.
+    /// Defaults to `file` itself.
+    fn file_name<'a>(&self, file: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(file)
+    }
+}
+
+/// The built-in layouts.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// The classic human readable layout, with each entry framed by
+    /// separator lines and a `> header (context)` line
+    #[default]
+    Text,
+    /// Like [`Format::Text`], but each entry is enclosed in a pair of fence
+    /// lines unique to the logging session, similar to a markdown code
+    /// fence. Content containing separator lines, or anything else that
+    /// looks like an entry, cannot be confused with the framing. A bare `>`
+    /// line ends the header block, so content cannot be read as fields.
+    ///
+    /// ```text
+    /// ~~~odebug-3f9a2c1b7e4d8a60
+    /// > header (src/lib.rs:42)
+    /// >   key = value
+    /// >
+    /// content
+    /// ~~~odebug-3f9a2c1b7e4d8a60
+    /// ```
+    Framed,
+    /// Markdown, for pasting into issues and reviews. Headers become `###`
+    /// headings with the context as a source link, and content goes into
+    /// fenced code blocks, tagged `rust` for [`ContentKind::Rust`] content.
+    /// Log files are written with an `.md` extension instead of `.log`.
+    ///
+    /// ````text
+    /// ### header — [src/lib.rs:42](src/lib.rs#L42)
+    ///
+    /// ```rust
+    /// impl Foo {}
+    /// ```
+    /// ````
+    Markdown,
+    /// A single line per entry, with newlines in the content escaped, for
+    /// high-volume logging that stays greppable. A `|` before the content
+    /// and a `]` in the header are escaped as well, so the line can be
+    /// split reliably.
+    ///
+    /// ```text
+    /// [header] src/lib.rs:42 n=3 | first line\nsecond line
+    /// ```
+    Compact,
+    /// One JSON object per line, for processing with other tools. Log
+    /// files are written with a `.jsonl` extension instead of `.log`.
+    ///
+    /// ```text
+    /// {"file":"debug.log","header":"header","context":"src/lib.rs:42","kind":"text","fields":{"n":3},"content":"content"}
+    /// ```
+    Json,
+}
+
+impl Format {
+    /// Writes `entry` to `out` in this format.
+    pub fn write_entry<W: Write + ?Sized>(&self, entry: &Entry, out: &mut W) -> io::Result<()> {
+        match self {
+            Format::Text => write_text(entry, out),
+            Format::Framed => write_framed(entry, out),
+            Format::Markdown => write_markdown(entry, out),
+            Format::Compact => write_compact(entry, out),
+            Format::Json => write_json(entry, out),
+        }
+    }
+
+    /// Returns the name of the file an entry for `file` is written to in
+    /// this format.
+    pub fn file_name<'a>(&self, file: &'a str) -> Cow<'a, str> {
+        match (self, file.strip_suffix(".log")) {
+            (Format::Markdown, Some(stem)) => Cow::Owned(format!("{}.md", stem)),
+            (Format::Json, Some(stem)) => Cow::Owned(format!("{}.jsonl", stem)),
+            _ => Cow::Borrowed(file),
+        }
+    }
+
+    /// Renders `entry` into a string in this format.
+    pub fn render(&self, entry: &Entry) -> String {
+        let mut buf = Vec::new();
+        // writing into a Vec cannot fail
+        let _ = self.write_entry(entry, &mut buf);
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+}
+
+impl Formatter for Format {
+    fn format(&self, entry: &Entry, out: &mut dyn Write) -> io::Result<()> {
+        self.write_entry(entry, out)
+    }
+
+    fn file_name<'a>(&self, file: &'a str) -> Cow<'a, str> {
+        Format::file_name(self, file)
+    }
+}
+
+fn write_text<W: Write + ?Sized>(entry: &Entry, writer: &mut W) -> io::Result<()> {
+    match (entry.header.as_deref(), entry.context.as_deref()) {
+        (Some(header), Some(context)) => {
+            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "> {0} ({1})", header, context)?;
+            write_field_lines(entry, writer)?;
+            writeln!(writer, "{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "{0}", entry.content)?;
+        },
+        (Some(header), None) => {
+            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "> {0}", header)?;
+            write_field_lines(entry, writer)?;
+            writeln!(writer, "{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "{0}", entry.content)?;
+        },
+        (None, Some(context)) => {
+            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "> [at {0}]", context)?;
+            write_field_lines(entry, writer)?;
+            writeln!(writer, "{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "{0}", entry.content)?;
+        },
+        (None, None) if !entry.fields.is_empty() => {
+            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "> ")?;
+            write_field_lines(entry, writer)?;
+            writeln!(writer, "{0}", SEPARATOR_LINE)?;
+            writeln!(writer, "{0}", entry.content)?;
+        },
+        (None, None) => {
+            writeln!(writer, "\n{0}", entry.content)?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Writes the fields of `entry` as an aligned table below the title line,
+/// one `>   key = value` line per field.
+fn write_field_lines<W: Write + ?Sized>(entry: &Entry, writer: &mut W) -> io::Result<()> {
+    let width = entry
+        .fields
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or_default();
+    for (key, value) in &entry.fields {
+        writeln!(
+            writer,
+            "{0}{1:<width$} = {2}",
+            FIELD_PREFIX,
+            key,
+            escape_line(&value.to_string())
+        )?;
+    }
+    Ok(())
+}
+
+fn write_framed<W: Write + ?Sized>(entry: &Entry, writer: &mut W) -> io::Result<()> {
+    let fence = session_fence();
+    writeln!(writer, "\n{0}", fence)?;
+    match (entry.header.as_deref(), entry.context.as_deref()) {
+        (Some(header), Some(context)) => writeln!(writer, "> {0} ({1})", header, context)?,
+        (Some(header), None) => writeln!(writer, "> {0}", header)?,
+        (None, Some(context)) => writeln!(writer, "> [at {0}]", context)?,
+        (None, None) => writeln!(writer, ">")?,
+    }
+    write_field_lines(entry, writer)?;
+    writeln!(writer, ">\n{0}", entry.content)?;
+    writeln!(writer, "{0}", fence)?;
+
+    Ok(())
+}
+
+fn write_markdown<W: Write + ?Sized>(entry: &Entry, writer: &mut W) -> io::Result<()> {
+    let source_link = |context: &str| match context.rsplit_once(':') {
+        Some((file, line)) if line.bytes().all(|b| b.is_ascii_digit()) => {
+            format!("[{0}]({1}#L{2})", context, file, line)
+        },
+        _ => format!("`{0}`", context),
+    };
+
+    match (entry.header.as_deref(), entry.context.as_deref()) {
+        (Some(header), Some(context)) => {
+            writeln!(writer, "\n### {0} — {1}\n", header, source_link(context))?;
+        },
+        (Some(header), None) => writeln!(writer, "\n### {0}\n", header)?,
+        (None, Some(context)) => writeln!(writer, "\n### {0}\n", source_link(context))?,
+        (None, None) => writeln!(writer)?,
+    }
+
+    if !entry.fields.is_empty() {
+        writeln!(writer, "| field | value |\n| --- | --- |")?;
+        for (key, value) in &entry.fields {
+            let value = escape_line(&value.to_string()).replace('|', "\\|");
+            writeln!(writer, "| {0} | {1} |", key, value)?;
+        }
+        writeln!(writer)?;
+    }
+
+    // the fence has to be longer than any run of backticks in the content
+    let longest_run = entry
+        .content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let language = match entry.kind {
+        ContentKind::Rust => "rust",
+        ContentKind::Text => "",
+    };
+    writeln!(writer, "{0}{1}", fence, language)?;
+    writeln!(writer, "{0}", entry.content)?;
+    writeln!(writer, "{0}", fence)?;
+
+    Ok(())
+}
+
+/// Escapes backslashes and line breaks so `text` fits on one line.
+fn escape_line(text: &str) -> Cow<'_, str> {
+    escape_compact(text, &[])
+}
+
+/// Like [`escape_line`], also escaping `special` characters with a
+/// backslash.
+fn escape_compact<'a>(text: &'a str, special: &[char]) -> Cow<'a, str> {
+    if !text.contains(|c| matches!(c, '\\' | '\n' | '\r') || special.contains(&c)) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c if special.contains(&c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+fn write_compact<W: Write + ?Sized>(entry: &Entry, writer: &mut W) -> io::Result<()> {
+    if let Some(header) = &entry.header {
+        write!(writer, "[{0}] ", escape_compact(header, &['|', ']']))?;
+    }
+    if let Some(context) = &entry.context {
+        write!(writer, "{0} ", escape_compact(context, &['|']))?;
+    }
+    for (key, value) in &entry.fields {
+        let value = value.to_string();
+        write!(writer, "{0}={1} ", key, escape_compact(&value, &['|']))?;
+    }
+    writeln!(writer, "| {0}", escape_line(&entry.content))
+}
+
+pub(crate) fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_json<W: Write + ?Sized>(entry: &Entry, writer: &mut W) -> io::Result<()> {
+    let optional = |value: &Option<String>| match value {
+        Some(value) => json_string(value),
+        None => "null".to_string(),
+    };
+    let kind = match entry.kind {
+        ContentKind::Rust => "rust",
+        ContentKind::Text => "text",
+    };
+    let mut fields = String::new();
+    for (key, value) in &entry.fields {
+        if !fields.is_empty() {
+            fields.push(',');
+        }
+        fields.push_str(&json_string(key));
+        fields.push(':');
+        match value {
+            Value::Bool(value) => fields.push_str(&value.to_string()),
+            Value::Int(value) => fields.push_str(&value.to_string()),
+            Value::UInt(value) => fields.push_str(&value.to_string()),
+            Value::Float(value) if value.is_finite() => fields.push_str(&format!("{:?}", value)),
+            Value::Float(_) => fields.push_str("null"),
+            Value::Str(value) => fields.push_str(&json_string(value)),
+        }
+    }
+    writeln!(
+        writer,
+        "{{\"file\":{0},\"header\":{1},\"context\":{2},\"kind\":\"{3}\",\"fields\":{{{4}}},\"content\":{5}}}",
+        json_string(&entry.file),
+        optional(&entry.header),
+        optional(&entry.context),
+        kind,
+        fields,
+        json_string(&entry.content)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_layout() {
+        let entry = Entry::new("expand.log", "impl Foo {}")
+            .with_header("Output")
+            .with_context("src/lib.rs:42")
+            .with_kind(ContentKind::Rust);
+
+        assert_eq!(
+            Format::Markdown.render(&entry),
+            "\n### Output — [src/lib.rs:42](src/lib.rs#L42)\n\n```rust\nimpl Foo {}\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_fence_outgrows_content() {
+        let entry = Entry::new("debug.log", "```\nnested\n````");
+        let rendered = Format::Markdown.render(&entry);
+        assert!(rendered.starts_with("\n`````\n"), "{}", rendered);
+        assert!(rendered.ends_with("\n`````\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_markdown_file_name() {
+        assert_eq!(Format::Markdown.file_name("expand.log"), "expand.md");
+        assert_eq!(Format::Markdown.file_name("notes.txt"), "notes.txt");
+        assert_eq!(Format::Text.file_name("expand.log"), "expand.log");
+        assert_eq!(Format::Json.file_name("expand.log"), "expand.jsonl");
+    }
+
+    #[test]
+    fn test_compact_layout() {
+        let entry = Entry::new("debug.log", "first\nsecond \\n")
+            .with_header("Header")
+            .with_context("src/lib.rs:42");
+        assert_eq!(
+            Format::Compact.render(&entry),
+            "[Header] src/lib.rs:42 | first\\nsecond \\\\n\n"
+        );
+        assert_eq!(
+            Format::Compact.render(&Entry::new("debug.log", "bare")),
+            "| bare\n"
+        );
+        let entry = Entry::new("debug.log", "a | b")
+            .with_header("[a] | b")
+            .with_field("pipe", " | ");
+        assert_eq!(
+            Format::Compact.render(&entry),
+            "[[a\\] \\| b] pipe= \\|  | a | b\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_in_every_format() {
+        let entry = Entry::new("debug.log", "content")
+            .with_header("Header")
+            .with_field("ident", "Foo")
+            .with_field("variants", 3)
+            .with_field("ratio", 0.5);
+
+        let text = Format::Text.render(&entry);
+        assert!(
+            text.contains("> Header\n>   ident    = Foo\n>   variants = 3\n>   ratio    = 0.5\n"),
+            "{}",
+            text
+        );
+        assert!(Format::Framed.render(&entry).contains(
+            "> Header\n>   ident    = Foo\n>   variants = 3\n>   ratio    = 0.5\n>\ncontent\n"
+        ));
+        assert_eq!(
+            Format::Compact.render(&entry),
+            "[Header] ident=Foo variants=3 ratio=0.5 | content\n"
+        );
+        assert!(Format::Json
+            .render(&entry)
+            .contains("\"fields\":{\"ident\":\"Foo\",\"variants\":3,\"ratio\":0.5}"));
+        assert!(Format::Markdown
+            .render(&entry)
+            .contains("| field | value |\n| --- | --- |\n| ident | Foo |\n"));
+    }
+
+    #[test]
+    fn test_json_layout() {
+        let entry = Entry::new("debug.log", "say \"hi\"\n\tbye").with_header("Header");
+        assert_eq!(
+            Format::Json.render(&entry),
+            "{\"file\":\"debug.log\",\"header\":\"Header\",\"context\":null,\
+             \"kind\":\"text\",\"fields\":{},\"content\":\"say \\\"hi\\\"\\n\\tbye\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_custom_formatter_through_trait_object() {
+        struct Upper;
+
+        impl Formatter for Upper {
+            fn format(&self, entry: &Entry, out: &mut dyn Write) -> io::Result<()> {
+                writeln!(out, "{}", entry.content.to_uppercase())
+            }
+        }
+
+        let formatters: Vec<Box<dyn Formatter>> = vec![Box::new(Format::Markdown), Box::new(Upper)];
+        let mut out = Vec::new();
+        formatters[1]
+            .format(&Entry::new("debug.log", "quiet"), &mut out)
+            .unwrap();
+        assert_eq!(out, b"QUIET\n");
+        assert_eq!(formatters[0].file_name("a.log"), "a.md");
+        assert_eq!(formatters[1].file_name("a.log"), "a.log");
+    }
+}
@@ -0,0 +1,441 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::entry::Entry;
+use crate::format::Format;
+use crate::name::validate_file_name;
+use crate::panic::{self, PANIC_FILE};
+
+/// The file [`Invocation`]s are listed in, one compact entry each.
+pub const INVOCATIONS_FILE: &str = "invocations.log";
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+/// Directories already cleaned of the files of finished processes.
+static CLEANED: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+thread_local! {
+    static ACTIVE: RefCell<Vec<Active>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Active {
+    // the file name without extension
+    stem: String,
+    entries: u64,
+}
+
+/// Routes every entry logged on the current thread to a file of its own,
+/// until dropped. Created by [`invocation`].
+#[must_use = "entries are only routed while the invocation is alive"]
+#[derive(Debug)]
+pub struct Invocation {
+    sequence: u64,
+    file: String,
+    input: String,
+    // the routing is per thread, so the guard must stay on its thread
+    _thread: PhantomData<*const ()>,
+}
+
+/// Starts routing the entries logged on the current thread to a new file,
+/// `name.<pid>.0007.log`, numbered in the order of the calls within the
+/// process. The process id keeps the files of parallel compiler processes
+/// apart, and characters not allowed in file names, like the `<` and `:`
+/// of `derive_Foo<T>` or `derive_my::Type`, are replaced with `_`.
+///
+/// Meant for proc-macros, to isolate the output of each expansion when a
+/// derive is applied to many types. Only the file output is redirected:
+/// filters, per-file formatters and other sinks see the entry unchanged,
+/// with the name it was logged to. The entry keeps its
+/// extension, so Markdown and JSON output stay in files of their kind.
+/// When the returned guard is dropped, the invocation is listed in
+/// [`INVOCATIONS_FILE`] with its input and number of entries written.
+/// Invocations nest, with entries going to the innermost one, and
+/// invocations without entries are left out of the index. Neither the
+/// index nor the panic log is ever redirected.
+///
+/// Parallel processes share the index, so it is appended to rather than
+/// cleared. Instead, the first process routing into a directory removes
+/// the files, and index lines, of processes that are no longer running.
+/// This needs `/proc`, so elsewhere old files are kept until `odebug
+/// clean`.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::odebug;
+///
+/// # odebug::with_debug_dir(std::env::temp_dir().join("odebug-doc-invocation"), || {
+/// let input = "Foo";
+/// let invocation = odebug::invocation(&format!("derive_{}", input)).input(input);
+/// odebug!(expand::Output("impl Debug for {} {{}}", input));
+/// assert!(invocation.file().starts_with("derive_Foo."));
+/// # });
+/// ```
+pub fn invocation(name: &str) -> Invocation {
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let stem = format!("{}.{}.{:04}", sanitize(name), std::process::id(), sequence);
+    ACTIVE.with(|active| {
+        active.borrow_mut().push(Active {
+            stem: stem.clone(),
+            entries: 0,
+        })
+    });
+    Invocation {
+        sequence,
+        file: format!("{}.log", stem),
+        input: name.to_string(),
+        _thread: PhantomData,
+    }
+}
+
+impl Invocation {
+    /// Sets the name of the input, usually the type a derive is applied
+    /// to, listed in the index. Defaults to the name of the invocation.
+    pub fn input(mut self, input: impl Into<String>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Returns the file entries are routed to, or the `.log` one of them
+    /// if entries with other extensions are logged.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Returns the sequence number of the invocation.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl Drop for Invocation {
+    fn drop(&mut self) {
+        let active = ACTIVE.try_with(|active| {
+            let mut active = active.borrow_mut();
+            let index = active.iter().rposition(|a| stem(&self.file) == a.stem)?;
+            Some(active.remove(index))
+        });
+        let Ok(Some(active)) = active else {
+            return;
+        };
+        if active.entries == 0 {
+            return;
+        }
+
+        let logger = crate::logger();
+        if let Err(e) = logger.log_entry(&self.index_entry(active.entries)) {
+            logger.handle_error(e);
+        }
+    }
+}
+
+impl Invocation {
+    /// The line listing the invocation in [`INVOCATIONS_FILE`], in
+    /// [`Format::Compact`] whatever the format of the logger.
+    fn index_entry(&self, entries: u64) -> Entry {
+        Entry::new(INVOCATIONS_FILE, self.file.as_str())
+            .with_header("INVOCATION")
+            .with_field("seq", self.sequence)
+            .with_field("input", self.input.as_str())
+            .with_field("entries", entries)
+            .with_format(Format::Compact)
+    }
+}
+
+/// Returns the file of the innermost invocation of the current thread for
+/// an entry to `file`. The index and the panic log are never routed.
+pub(crate) fn route(file: &str) -> Option<String> {
+    let is_panic_file = |name: &str| validate_file_name(name).is_ok_and(|name| name == file);
+    if file == INVOCATIONS_FILE
+        || is_panic_file(PANIC_FILE)
+        || panic::panic_file().is_some_and(is_panic_file)
+    {
+        return None;
+    }
+    let extension = Path::new(file)
+        .extension()
+        .map_or("log".into(), |extension| extension.to_string_lossy());
+    ACTIVE
+        .try_with(|active| {
+            let active = active.borrow();
+            let innermost = active.last()?;
+            Some(format!("{}.{}", innermost.stem, extension))
+        })
+        .ok()
+        .flatten()
+}
+
+/// Counts an entry written to `routed`, a file returned by [`route`].
+pub(crate) fn count(routed: &str) {
+    let _ = ACTIVE.try_with(|active| {
+        let mut active = active.borrow_mut();
+        if let Some(active) = active.iter_mut().rev().find(|a| a.stem == stem(routed)) {
+            active.entries += 1;
+        }
+    });
+}
+
+/// Removes the invocations of processes that are no longer running from
+/// the index in `dir`, together with their files, once per process. Only
+/// files listed in the index are removed.
+pub(crate) fn clean_stale(dir: &Path) {
+    if !CLEANED
+        .lock()
+        .is_ok_and(|mut cleaned| cleaned.insert(dir.to_path_buf()))
+    {
+        return;
+    }
+
+    // lines appended by other processes meanwhile go to a new index
+    let index = dir.join(INVOCATIONS_FILE);
+    let moved = dir.join(format!("invocations.{}.tmp", std::process::id()));
+    if fs::rename(&index, &moved).is_err() {
+        return;
+    }
+    let lines = fs::read_to_string(&moved).unwrap_or_default();
+    let mut kept = String::new();
+    let mut stale = HashSet::new();
+    for line in lines.lines() {
+        match line.rsplit_once(" | ") {
+            Some((_, file)) if is_stale(file) => {
+                stale.insert(stem(file).to_string());
+            },
+            _ => {
+                kept.push_str(line);
+                kept.push('\n');
+            },
+        }
+    }
+    let _ = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&index)
+        .and_then(|mut index| index.write_all(kept.as_bytes()));
+    let _ = fs::remove_file(&moved);
+
+    // the files of an invocation share its stem, whatever their extension
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        if stale.contains(stem(&entry.file_name().to_string_lossy())) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Whether the invocation that wrote `file` belongs to a process that has
+/// exited, as far as that can be told.
+fn is_stale(file: &str) -> bool {
+    let Some(pid) = stem(file).rsplit('.').nth(1) else {
+        return false;
+    };
+    pid.parse().is_ok_and(crate::session::has_exited)
+}
+
+/// Strips the extension from a routed file name.
+fn stem(file: &str) -> &str {
+    file.rsplit_once('.').map_or(file, |(stem, _)| stem)
+}
+
+/// Replaces the characters of `name` that are not allowed in a file name,
+/// or could make it a path, with `_`.
+fn sanitize(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() || c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect();
+    match sanitized.as_str() {
+        "" | "." | ".." => "invocation".to_string(),
+        _ => sanitized,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::entry::Value;
+    use crate::sink::RingBufferSink;
+    use crate::{parse, testing, Logger};
+
+    #[test]
+    fn test_entries_are_routed_to_the_innermost_invocation() {
+        let dir = crate::test_dir("invocation-route");
+        let (outer_file, inner_file) = crate::with_debug_dir(&dir, || {
+            let logger = crate::logger();
+            logger.log("debug.log", Some("A"), "before").unwrap();
+
+            let outer = invocation("derive_Foo").input("Foo");
+            logger.log("expand.log", Some("A"), "outer").unwrap();
+            let inner_file = {
+                let inner = invocation("derive_Bar");
+                assert!(inner.sequence() > outer.sequence());
+                logger.log("expand.log", Some("A"), "inner").unwrap();
+                inner.file().to_string()
+            };
+            logger.log("expand.log", Some("A"), "outer again").unwrap();
+            let outer_file = outer.file().to_string();
+            drop(outer);
+            logger.log("debug.log", Some("A"), "after").unwrap();
+            (outer_file, inner_file)
+        });
+
+        let read = |file: &str| -> Vec<_> {
+            parse::read_file(dir.join(file))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        let contents = |file: &str| -> Vec<String> {
+            read(file).into_iter().map(|entry| entry.content).collect()
+        };
+        assert_eq!(contents("debug.log"), ["before", "after"]);
+        assert_eq!(contents(&outer_file), ["outer", "outer again"]);
+        assert_eq!(contents(&inner_file), ["inner"]);
+        assert!(!dir.join("expand.log").exists());
+
+        let index = read(INVOCATIONS_FILE);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index[1].content, outer_file);
+        assert_eq!(index[1].field("input"), Some(&Value::from("Foo")));
+        assert_eq!(index[1].field("entries"), Some(&Value::from(2)));
+    }
+
+    #[test]
+    fn test_only_the_file_output_is_routed() {
+        let dir = crate::test_dir("invocation-file-output");
+        let buffer = RingBufferSink::new(16);
+        let logger = Logger::builder()
+            .dir(&dir)
+            .file_formatter("tokens.log", Format::Compact)
+            .filter(|entry| entry.file != "skipped.log")
+            .sink(buffer.clone())
+            .build();
+
+        let file = crate::with_debug_dir(&dir, || {
+            let invocation = invocation("derive_Foo");
+            logger.log("tokens.log", None, "struct").unwrap();
+            logger.log("skipped.log", None, "filtered").unwrap();
+            logger.log("expand.md", None, "markdown").unwrap();
+            invocation.file().to_string()
+        });
+
+        let buffered = buffer.entries();
+        assert_eq!(buffered.len(), 2);
+        assert_eq!(buffered[0].file, "tokens.log");
+        assert!(buffered[0].fields.is_empty());
+        let content = fs::read_to_string(dir.join(&file)).unwrap();
+        assert_eq!(content, "| struct\n");
+        let markdown = dir.join(file.replace(".log", ".md"));
+        assert!(markdown.is_file());
+
+        // filtered entries are not counted
+        let index = parse::read_file(dir.join(INVOCATIONS_FILE))
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(index[0].field("entries"), Some(&Value::from(2)));
+    }
+
+    #[test]
+    fn test_index_stays_compact_under_a_json_logger() {
+        let dir = crate::test_dir("invocation-json");
+        let logger = Logger::builder().dir(&dir).format(Format::Json).build();
+
+        let invocation = invocation("derive_Json");
+        logger.log("expand.log", None, "routed").unwrap();
+        logger.log_entry(&invocation.index_entry(1)).unwrap();
+
+        let routed = invocation.file().replace(".log", ".jsonl");
+        let entries: Vec<_> = parse::read_file(dir.join(&routed)).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        assert!(!dir.join("invocations.jsonl").exists());
+        let index = parse::read_file(dir.join(INVOCATIONS_FILE))
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(index[0].content, invocation.file());
+        assert_eq!(index[0].field("input"), Some(&Value::from("derive_Json")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_index_is_shared_and_stale_invocations_are_removed() {
+        let dir = crate::test_dir("invocation-stale");
+        // no process has an id this large
+        let dead = "derive_Old.4000000000.0001";
+        let alive = format!("derive_Alive.{}.0001.log", std::process::id());
+        fs::write(
+            dir.join(INVOCATIONS_FILE),
+            format!(
+                "[INVOCATION] seq=1 | {0}.log\n[INVOCATION] seq=1 | {1}\n",
+                dead, alive
+            ),
+        )
+        .unwrap();
+        for file in [format!("{}.log", dead), format!("{}.md", dead), alive.clone()] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        // not listed in the index, so kept even though it looks alike
+        fs::write(dir.join("report.4000000000.0002.log"), "").unwrap();
+
+        let logger = Logger::builder().dir(&dir).build();
+        crate::with_debug_dir(&dir, || {
+            // the index entry is written to the thread's directory on drop
+            let _invocation = invocation("derive_New");
+            logger.log("expand.log", None, "routed").unwrap();
+        });
+
+        assert!(!dir.join(format!("{}.log", dead)).exists());
+        assert!(!dir.join(format!("{}.md", dead)).exists());
+        assert!(dir.join(&alive).exists());
+        assert!(dir.join("report.4000000000.0002.log").exists());
+        let index = fs::read_to_string(dir.join(INVOCATIONS_FILE)).unwrap();
+        let lines: Vec<&str> = index.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", index);
+        assert!(lines[0].ends_with(&alive), "{}", index);
+        assert!(lines[1].contains("derive_New."), "{}", index);
+    }
+
+    #[test]
+    fn test_file_names() {
+        let invocation = invocation("derive_my::Type<T>");
+        let expected = format!(
+            "derive_my__Type_T_.{}.{:04}.log",
+            std::process::id(),
+            invocation.sequence()
+        );
+        assert_eq!(invocation.file(), expected);
+        assert!(validate_file_name(invocation.file()).is_ok());
+        assert_eq!(sanitize("../a b"), ".._a_b");
+        assert_eq!(sanitize(""), "invocation");
+
+        assert_eq!(
+            route("expand.jsonl"),
+            Some(expected.replace(".log", ".jsonl"))
+        );
+        assert_eq!(route(INVOCATIONS_FILE), None);
+        assert_eq!(route(PANIC_FILE), None);
+    }
+
+    #[test]
+    fn test_empty_invocations_are_not_indexed() {
+        let capture = testing::capture();
+        drop(invocation("derive_Empty"));
+        assert!(capture.entries().is_empty());
+    }
+}
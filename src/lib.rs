@@ -1,19 +1,44 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", "README.md"))]
 
-use once_cell::sync::Lazy;
-use std::collections::HashSet;
 use std::env;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+#[cfg(feature = "use_workspace")]
+use std::fs;
 use std::path::PathBuf;
 
-pub static DEBUG_DIR: Lazy<PathBuf> = Lazy::new(|| {
-    let debug_dir = determine_debug_dir();
-    fs::create_dir_all(&debug_dir).unwrap_or_else(|e| {
-        eprintln!("Failed to create debug directory: {}", e);
-    });
-    debug_dir
-});
+mod callsite;
+mod entry;
+mod error;
+mod exit;
+mod format;
+mod invocation;
+mod location;
+#[cfg(feature = "log")]
+pub mod log_bridge;
+mod logger;
+mod name;
+mod panic;
+pub mod parse;
+pub mod report;
+mod session;
+pub mod sink;
+pub mod testing;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+
+pub use callsite::{suppressed, SUPPRESSED_FILE};
+pub use entry::{ContentKind, Entry, Value};
+pub use error::{health, last_error, Error, ErrorKind, ErrorPolicy, Health, Operation};
+pub use format::{session_fence, Format, Formatter};
+pub use invocation::{invocation, Invocation, INVOCATIONS_FILE};
+pub use location::{
+    debug_dir, find_location, location, reset_debug_dir, reset_thread_debug_dir, set_debug_dir,
+    set_thread_debug_dir, with_debug_dir, Location, LOCATION_FILE,
+};
+pub use logger::{logger, set_logger, Logger, LoggerBuilder, COMPACT_ENV};
+pub use name::{validate_file_name, ALLOWED_EXTENSIONS};
+pub use panic::{install_panic_hook, install_panic_hook_to, PANIC_FILE};
+pub use session::session_file;
+pub use sink::{FileMode, FileSink, Sink};
 
 /// Determines the appropriate debug directory based on feature flags
 fn determine_debug_dir() -> PathBuf {
@@ -55,7 +80,6 @@ fn default_debug_dir() -> PathBuf {
     }
 }
 
-#[cfg(feature = "output_to_target")]
 fn find_target_dir() -> Option<PathBuf> {
     if let Ok(dir) = std::env::var("CARGO_TARGET_DIR") {
         return Some(PathBuf::from(dir));
@@ -72,6 +96,15 @@ fn find_target_dir() -> Option<PathBuf> {
     Some(current.join("target"))
 }
 
+/// Returns the workspace root, or the current directory if there is none
+fn workspace_dir() -> PathBuf {
+    #[cfg(feature = "use_workspace")]
+    if let Some(root) = find_workspace_root() {
+        return root;
+    }
+    env::current_dir().unwrap_or_default()
+}
+
 #[cfg(feature = "use_workspace")]
 fn find_workspace_root() -> Option<PathBuf> {
     let mut current_dir = env::current_dir().ok()?;
@@ -92,11 +125,7 @@ fn find_workspace_root() -> Option<PathBuf> {
 }
 
 #[doc(hidden)]
-const SEPARATOR_LINE: &str = "-----------------------------------------------------------";
-
-#[doc(hidden)]
-static INITIALIZED_FILES: Lazy<std::sync::Mutex<HashSet<String>>> =
-    Lazy::new(|| std::sync::Mutex::new(HashSet::new()));
+pub const SEPARATOR_LINE: &str = "-----------------------------------------------------------";
 
 /// Writes content to a debug log file with optional header and context information.
 ///
@@ -128,55 +157,9 @@ pub fn write_to_debug_file(
     header: Option<&str>,
     context: Option<&str>,
 ) -> std::io::Result<()> {
-    let _ = fs::create_dir_all(&*DEBUG_DIR);
-
-    let path = DEBUG_DIR.join(filename);
-
-    let should_clear = {
-        let mut initialized = INITIALIZED_FILES.lock().unwrap();
-        if !initialized.contains(filename) {
-            initialized.insert(filename.to_string());
-            true
-        } else {
-            false
-        }
-    };
-
-    if should_clear {
-        let _ = fs::remove_file(&path);
-    }
-
-    // buffered writer for better performance
-    let file = OpenOptions::new().create(true).append(true).open(&path)?;
-    let mut writer = std::io::BufWriter::new(file);
-
-    match (header, context) {
-        (Some(header), Some(context)) => {
-            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
-            writeln!(writer, "> {0} ({1})", header, context)?;
-            writeln!(writer, "{0}", SEPARATOR_LINE)?;
-            writeln!(writer, "{0}", content)?;
-        },
-        (Some(header), None) => {
-            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
-            writeln!(writer, "> {0}", header)?;
-            writeln!(writer, "{0}", SEPARATOR_LINE)?;
-            writeln!(writer, "{0}", content)?;
-        },
-        (None, Some(context)) => {
-            writeln!(writer, "\n{0}", SEPARATOR_LINE)?;
-            writeln!(writer, "> [at {0}]", context)?;
-            writeln!(writer, "{0}", SEPARATOR_LINE)?;
-            writeln!(writer, "{0}", content)?;
-        },
-        (None, None) => {
-            writeln!(writer, "\n{0}", content)?;
-        },
-    }
-
-    writer.flush()?;
-
-    Ok(())
+    logger()
+        .log_entry(&__private::entry(filename, content, header, context))
+        .map_err(std::io::Error::from)
 }
 
 #[macro_export]
@@ -215,230 +198,711 @@ pub fn write_to_debug_file(
 /// odebug!("Important message".with_header("IMPORTANT"));
 /// odebug!("Error details".to_file("errors.log").with_header("ERROR"));
 /// ```
+///
+/// Compact single-line entries, for high-volume logging:
+/// ```
+/// use odebug::odebug;
+/// for token in ["struct", "Foo", "{", "}"] {
+///     odebug!(compact; tokens::Token(token));
+/// }
+/// ```
+///
+/// Conditional logging, with the condition compiled out together with the
+/// rest of the call in release builds. It comes before any other prefix,
+/// and ends at the first `;`. Without one, an `if` expression is logged as
+/// content:
+/// ```
+/// use odebug::odebug;
+/// let name = "Foo";
+/// odebug!(if name == "Foo"; derive::Expanded("impl for {}", name));
+/// odebug!(if name.len() > 2; compact; "long name {}", name);
+/// odebug!(if name.is_empty() { "anonymous" } else { name });
+/// ```
 macro_rules! odebug {
+    (@when [$($cond:tt)*] $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            if $($cond)* {
+                $crate::odebug!($($args)*)
+            }
+        }
+    };
+    (@content $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            $crate::__private::emit($crate::__odebug_entry!([] $($args)*))
+        }
+    };
+    (if $($rest:tt)*) => {
+        $crate::__odebug_if!(odebug [] $($rest)*)
+    };
+    (compact; $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            $crate::__private::emit(
+                $crate::__odebug_entry!([] $($args)*).with_format($crate::Format::Compact)
+            )
+        }
+    };
     ($($args:tt)*) => {
+        $crate::odebug!(@content $($args)*)
+    };
+}
+
+#[macro_export]
+/// Logs a token stream, or other Rust source, with the same syntax as
+/// [`odebug!`].
+///
+/// The content is marked as [`ContentKind::Rust`], so formats that know
+/// about code, like [`Format::Markdown`], can present it as such.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::odebug_tokens;
+/// let expanded = "impl Foo { fn new() -> Self { Foo } }";
+/// odebug_tokens!(expand::Output(expanded));
+/// ```
+macro_rules! odebug_tokens {
+    (@when [$($cond:tt)*] $($args:tt)*) => {
         #[cfg(any(debug_assertions, feature = "always_log"))]
         {
-            $crate::__internal_debug_macro!($($args)*)
+            if $($cond)* {
+                $crate::odebug_tokens!($($args)*)
+            }
+        }
+    };
+    (@content $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            $crate::__private::emit(
+                $crate::__odebug_entry!([] $($args)*).with_kind($crate::ContentKind::Rust)
+            )
+        }
+    };
+    (if $($rest:tt)*) => {
+        $crate::__odebug_if!(odebug_tokens [] $($rest)*)
+    };
+    (compact; $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            $crate::__private::emit(
+                $crate::__odebug_entry!([] $($args)*)
+                    .with_kind($crate::ContentKind::Rust)
+                    .with_format($crate::Format::Compact)
+            )
+        }
+    };
+    ($($args:tt)*) => {
+        $crate::odebug_tokens!(@content $($args)*)
+    };
+}
+
+#[macro_export]
+/// Logs like [`odebug!`], but only if `cond` holds.
+///
+/// A shorthand for `odebug!(if cond; ...)`. Neither the condition nor the
+/// content is evaluated in release builds without the `always_log` feature.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::odebug_if;
+/// let ident = "MyStruct";
+/// odebug_if!(ident == "MyStruct", derive::Input("deriving for {}", ident));
+/// ```
+macro_rules! odebug_if {
+    ($cond:expr, $($args:tt)*) => {
+        $crate::odebug!(if $cond; $($args)*)
+    };
+}
+
+#[macro_export]
+/// Logs like [`odebug!`], but returns `Result<(), odebug::Error>` instead
+/// of handing failures to the [`ErrorPolicy`] of the logger.
+///
+/// Meant for code that depends on its log output, like snapshot tooling.
+/// The [`Error`] carries the [`Operation`] that failed and, for the file
+/// output, the resolved path. In release builds without the `always_log`
+/// feature nothing is evaluated and the result is always `Ok(())`.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::try_odebug;
+/// # fn main() -> Result<(), odebug::Error> {
+/// try_odebug!(snapshot::Expanded("impl Foo {{}}"))?;
+///
+/// let file = "../outside.log";
+/// let error = try_odebug!(file => "rejected").unwrap_err();
+/// assert_eq!(error.kind(), odebug::ErrorKind::InvalidName);
+/// assert_eq!(error.operation(), Some(odebug::Operation::Validate));
+/// # Ok(())
+/// # }
+/// ```
+macro_rules! try_odebug {
+    (@when [$($cond:tt)*] $($args:tt)*) => {{
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        let result = if $($cond)* {
+            $crate::try_odebug!($($args)*)
+        } else {
+            Ok(())
+        };
+        #[cfg(not(any(debug_assertions, feature = "always_log")))]
+        let result = Ok::<(), $crate::Error>(());
+        result
+    }};
+    (@content $($args:tt)*) => {{
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        let result = $crate::__private::try_emit($crate::__odebug_entry!([] $($args)*));
+        #[cfg(not(any(debug_assertions, feature = "always_log")))]
+        let result = Ok::<(), $crate::Error>(());
+        result
+    }};
+    (if $($rest:tt)*) => {
+        $crate::__odebug_if!(try_odebug [] $($rest)*)
+    };
+    (compact; $($args:tt)*) => {{
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        let result = $crate::__private::try_emit(
+            $crate::__odebug_entry!([] $($args)*).with_format($crate::Format::Compact)
+        );
+        #[cfg(not(any(debug_assertions, feature = "always_log")))]
+        let result = Ok::<(), $crate::Error>(());
+        result
+    }};
+    ($($args:tt)*) => {
+        $crate::try_odebug!(@content $($args)*)
+    };
+}
+
+#[macro_export]
+/// Logs like [`odebug!`], but only the first time this call site is hit.
+///
+/// Later hits are counted, and a summary of the suppressed counts of all
+/// rate-limited call sites is written to [`SUPPRESSED_FILE`] when the
+/// process exits. The content is not even formatted for suppressed hits.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::odebug_once;
+/// for i in 0..100 {
+///     odebug_once!(derive::Input("first input: {}", i));
+/// }
+/// ```
+macro_rules! odebug_once {
+    ($($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            static SITE: $crate::__private::CallSite =
+                $crate::__private::CallSite::new(concat!(file!(), ":", line!(), ":", column!()));
+            if SITE.once() {
+                $crate::__private::emit($crate::__odebug_entry!([] $($args)*))
+            }
+        }
+    };
+}
+
+#[macro_export]
+/// Logs like [`odebug!`], but only the first and then every `n`th time this
+/// call site is hit.
+///
+/// Suppressed hits are summarized at exit, see [`odebug_once!`].
+///
+/// # Examples
+///
+/// ```
+/// use odebug::odebug_every;
+/// for i in 0..100 {
+///     odebug_every!(10, "iteration {}", i);
+/// }
+/// ```
+macro_rules! odebug_every {
+    ($n:expr, $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            static SITE: $crate::__private::CallSite =
+                $crate::__private::CallSite::new(concat!(file!(), ":", line!(), ":", column!()));
+            if SITE.every($n) {
+                $crate::__private::emit($crate::__odebug_entry!([] $($args)*))
+            }
+        }
+    };
+}
+
+#[macro_export]
+/// Logs like [`odebug!`], but at most once per `period` from this call site.
+///
+/// Suppressed hits are summarized at exit, see [`odebug_once!`].
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use odebug::odebug_throttle;
+/// for i in 0..100 {
+///     odebug_throttle!(Duration::from_millis(100), "progress: {}", i);
+/// }
+/// ```
+macro_rules! odebug_throttle {
+    ($period:expr, $($args:tt)*) => {
+        #[cfg(any(debug_assertions, feature = "always_log"))]
+        {
+            static SITE: $crate::__private::CallSite =
+                $crate::__private::CallSite::new(concat!(file!(), ":", line!(), ":", column!()));
+            if SITE.throttle($period) {
+                $crate::__private::emit($crate::__odebug_entry!([] $($args)*))
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::callsite::CallSite;
+    pub use crate::name::check_file_name;
+    use crate::Entry;
+
+    /// Builds the entry for a macro call.
+    pub fn entry(
+        filename: &str,
+        content: &str,
+        header: Option<&str>,
+        context: Option<&str>,
+    ) -> Entry {
+        let mut entry = Entry::new(filename, content);
+        entry.header = header.map(str::to_string);
+        entry.context = context.map(str::to_string);
+        entry
+    }
+
+    /// Logs the entry of a macro call through the global logger.
+    pub fn emit(entry: Entry) {
+        let logger = crate::logger();
+        if let Err(e) = logger.log_entry(&entry) {
+            logger.handle_error(e);
         }
+    }
+
+    /// Logs the entry of a [`try_odebug!`] call through the global logger.
+    pub fn try_emit(entry: Entry) -> Result<(), crate::Error> {
+        crate::logger().log_entry(&entry)
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+/// Tells `$mac!(if cond; ...)` from `$mac!(if a { b } else { c })`: the
+/// tokens up to the first top-level `;` are the condition, unless there is
+/// none or an `else` comes first, which makes the call an `if` expression
+/// to log.
+macro_rules! __odebug_if {
+    ($mac:ident [$($cond:tt)*] ; $($args:tt)*) => {
+        $crate::$mac!(@when [$($cond)*] $($args)*)
+    };
+    ($mac:ident [$($cond:tt)*] else $($rest:tt)*) => {
+        $crate::$mac!(@content if $($cond)* else $($rest)*)
+    };
+    ($mac:ident [$($cond:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__odebug_if!($mac [$($cond)* $next] $($rest)*)
+    };
+    ($mac:ident [$($cond:tt)*]) => {
+        $crate::$mac!(@content if $($cond)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+/// Splits the macro arguments at the first top-level `;` into the content
+/// and the structured fields, and builds the entry.
+///
+/// The arguments are scanned eight tokens per step, so calls of up to about
+/// a thousand top-level tokens stay within the default recursion limit.
+macro_rules! __odebug_entry {
+    ([$($head:tt)*] ; $($fields:tt)*) => {
+        $crate::__internal_debug_macro!($($head)*)
+            .with_fields($crate::__odebug_fields!([] $($fields)*))
+    };
+    ([$($head:tt)*] $a:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a] ; $($fields)*)
+    };
+    ([$($head:tt)*] $a:tt $b:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b] ; $($fields)*)
+    };
+    ([$($head:tt)*] $a:tt $b:tt $c:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b $c] ; $($fields)*)
+    };
+    ([$($head:tt)*] $a:tt $b:tt $c:tt $d:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b $c $d] ; $($fields)*)
+    };
+    ([$($head:tt)*] $a:tt $b:tt $c:tt $d:tt $e:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b $c $d $e] ; $($fields)*)
+    };
+    ([$($head:tt)*] $a:tt $b:tt $c:tt $d:tt $e:tt $f:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b $c $d $e $f] ; $($fields)*)
+    };
+    ([$($head:tt)*] $a:tt $b:tt $c:tt $d:tt $e:tt $f:tt $g:tt ; $($fields:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b $c $d $e $f $g] ; $($fields)*)
+    };
+    // the arms above rule out a `;` among the next eight tokens
+    ([$($head:tt)*] $a:tt $b:tt $c:tt $d:tt $e:tt $f:tt $g:tt $h:tt $($rest:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $a $b $c $d $e $f $g $h] $($rest)*)
+    };
+    ([$($head:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__odebug_entry!([$($head)* $next] $($rest)*)
+    };
+    ([$($head:tt)*]) => {
+        $crate::__internal_debug_macro!($($head)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+/// Collects the segments of `a::b::c::Header(...)` into the file `a/b/c.log`.
+macro_rules! __odebug_path {
+    ([$($path:ident)+] $next:ident :: $($rest:tt)+) => {
+        $crate::__odebug_path!([$($path)+ $next] $($rest)+)
+    };
+
+    ([$first:ident $($path:ident)*] $header:ident($content:expr)) => {{
+        let context = format!("{}:{}", file!(), line!());
+        $crate::__private::entry(
+            concat!(stringify!($first) $(, "/", stringify!($path))*, ".log"),
+            &$content.to_string(),
+            Some(stringify!($header)),
+            Some(&context)
+        )
+    }};
+
+    ([$first:ident $($path:ident)*] $header:ident($fmt:expr, $($arg:tt)+)) => {{
+        let context = format!("{}:{}", file!(), line!());
+        let content = format!($fmt, $($arg)+);
+        $crate::__private::entry(
+            concat!(stringify!($first) $(, "/", stringify!($path))*, ".log"),
+            &content,
+            Some(stringify!($header)),
+            Some(&context)
+        )
+    }};
+
+    ([$first:ident $($path:ident)*] ($content:expr)) => {{
+        let context = format!("{}:{}", file!(), line!());
+        $crate::__private::entry(
+            concat!(stringify!($first) $(, "/", stringify!($path))*, ".log"),
+            &$content.to_string(),
+            None,
+            Some(&context)
+        )
+    }};
+
+    ([$first:ident $($path:ident)*] ($fmt:expr, $($arg:tt)+)) => {{
+        let context = format!("{}:{}", file!(), line!());
+        let content = format!($fmt, $($arg)+);
+        $crate::__private::entry(
+            concat!(stringify!($first) $(, "/", stringify!($path))*, ".log"),
+            &content,
+            None,
+            Some(&context)
+        )
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+/// Checks a file name at compile time if it is a string literal.
+macro_rules! __odebug_check_file {
+    ($file:literal) => {
+        const _: () = $crate::__private::check_file_name($file);
+    };
+    ($($file:tt)+) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+/// Turns `key = value, key = %value, key = ?value` into a list of fields.
+macro_rules! __odebug_fields {
+    ([$($out:expr,)*]) => {
+        ::std::vec![$($out,)*]
+    };
+    ([$($out:expr,)*] $key:ident = % $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__odebug_fields!(
+            [$($out,)* (
+                ::std::string::String::from(stringify!($key)),
+                $crate::Value::Str(format!("{}", $value)),
+            ),]
+            $($($rest)*)?
+        )
+    };
+    ([$($out:expr,)*] $key:ident = ? $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__odebug_fields!(
+            [$($out,)* (
+                ::std::string::String::from(stringify!($key)),
+                $crate::Value::Str(format!("{:?}", $value)),
+            ),]
+            $($($rest)*)?
+        )
+    };
+    ([$($out:expr,)*] $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__odebug_fields!(
+            [$($out,)* (
+                ::std::string::String::from(stringify!($key)),
+                $crate::Value::from(::std::clone::Clone::clone(&$value)),
+            ),]
+            $($($rest)*)?
+        )
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __internal_debug_macro {
+    // nested path syntax, the leading segments name subdirectories
+    ($dir:ident :: $file:ident :: $($rest:tt)+) => {
+        $crate::__odebug_path!([$dir $file] $($rest)+)
+    };
+
     // path-like syntax with file and header
     ($file:ident::$header:ident($content:expr)) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             &format!("{}.log", stringify!($file)),
             &$content.to_string(),
             Some(stringify!($header)),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // path-like syntax with file and header, formatted content
     ($file:ident::$header:ident($fmt:expr, $($arg:tt)+)) => {{
         let context = format!("{}:{}", file!(), line!());
         let content = format!($fmt, $($arg)+);
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             &format!("{}.log", stringify!($file)),
             &content,
             Some(stringify!($header)),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // path-like syntax with just file
     ($file:ident::($content:expr)) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             &format!("{}.log", stringify!($file)),
             &$content.to_string(),
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // path-like syntax with just file, formatted content
     ($file:ident::($fmt:expr, $($arg:tt)+)) => {{
         let context = format!("{}:{}", file!(), line!());
         let content = format!($fmt, $($arg)+);
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             &format!("{}.log", stringify!($file)),
             &content,
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // just header syntax
     (::$header:ident($content:expr)) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             "debug.log",
             &$content.to_string(),
             Some(stringify!($header)),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // just header syntax with formatted content
     (::$header:ident($fmt:expr, $($arg:tt)+)) => {{
         let context = format!("{}:{}", file!(), line!());
         let content = format!($fmt, $($arg)+);
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             "debug.log",
             &content,
             Some(stringify!($header)),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
+    }};
+
+    // string literal filename support (keeping => syntax), the name is
+    // checked at compile time if it is a literal
+    ($file:tt => $content:expr) => {{
+        $crate::__odebug_check_file!($file);
+        let context = format!("{}:{}", file!(), line!());
+        $crate::__private::entry(
+            $file,
+            &$content.to_string(),
+            None,
+            Some(&context)
+        )
+    }};
+
+    ($file:tt => $fmt:expr, $($arg:tt)+) => {{
+        $crate::__odebug_check_file!($file);
+        let context = format!("{}:{}", file!(), line!());
+        let content = format!($fmt, $($arg)*);
+        $crate::__private::entry(
+            $file,
+            &content,
+            None,
+            Some(&context)
+        )
     }};
 
-    // string literal filename support (keeping => syntax)
+    // filename expressions
     ($file:expr => $content:expr) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             $file,
             &$content.to_string(),
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
-    // string literal filename with formatted content
     ($file:expr => $fmt:expr, $($arg:tt)+) => {{
         let context = format!("{}:{}", file!(), line!());
         let content = format!($fmt, $($arg)*);
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             $file,
             &content,
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // method chaining for literals
-    ($content:literal.to_file($file:expr)) => {{
+    ($content:literal.to_file($($file:tt)+)) => {{
+        $crate::__odebug_check_file!($($file)+);
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
-            $file,
+        $crate::__private::entry(
+            $($file)+,
             &$content.to_string(),
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     ($content:literal.with_header($header:expr)) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             "debug.log",
             &$content.to_string(),
             Some(&$header.to_string()),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // combined method chaining for literals
-    ($content:literal.to_file($file:expr).with_header($header:expr)) => {{
+    ($content:literal.to_file($($file:tt)+).with_header($header:expr)) => {{
+        $crate::__odebug_check_file!($($file)+);
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
-            $file,
+        $crate::__private::entry(
+            $($file)+,
             &$content.to_string(),
             Some(&$header.to_string()),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // method chaining for identifiers
-    ($content:ident.to_file($file:expr)) => {{
+    ($content:ident.to_file($($file:tt)+)) => {{
+        $crate::__odebug_check_file!($($file)+);
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
-            $file,
+        $crate::__private::entry(
+            $($file)+,
             &$content.to_string(),
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     ($content:ident.with_header($header:expr)) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             "debug.log",
             &$content.to_string(),
             Some(&$header.to_string()),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
-    ($content:ident.to_file($file:expr).with_header($header:expr)) => {{
+    ($content:ident.to_file($($file:tt)+).with_header($header:expr)) => {{
+        $crate::__odebug_check_file!($($file)+);
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
-            $file,
+        $crate::__private::entry(
+            $($file)+,
             &$content.to_string(),
             Some(&$header.to_string()),
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // simple content (default file, no header)
     ($content:expr) => {{
         let context = format!("{}:{}", file!(), line!());
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             "debug.log",
             &$content.to_string(),
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 
     // format string (default file, no header)
     ($fmt:expr, $($arg:tt)+) => {{
         let context = format!("{}:{}", file!(), line!());
         let content = format!($fmt, $($arg)+);
-        $crate::write_to_debug_file(
+        $crate::__private::entry(
             "debug.log",
             &content,
             None,
             Some(&context)
-        ).unwrap_or_else(|e| eprintln!("Failed to write debug log: {}", e))
+        )
     }};
 }
 
+/// Returns an empty directory for the files of a single test. The name
+/// includes the process id, so overlapping test runs, e.g. with different
+/// features, don't collide.
 #[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("odebug-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// the macros expand to nothing in release builds, leaving the tests both
+// without output to check and with unused variables
+#[cfg(all(test, any(debug_assertions, feature = "always_log")))]
 mod tests {
-    use once_cell::sync::Lazy;
     use std::fs;
     use std::path::Path;
-    use std::sync::Mutex;
-
-    static TEST_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
-
-    fn cleanup_test_logs() {
-        let debug_dir = crate::DEBUG_DIR.as_path();
-        let files = ["debug.log", "custom.log", "test.log"];
-        for file in files {
-            let _ = fs::remove_file(debug_dir.join(file));
-        }
-    }
 
     #[test]
     fn test_default_variants() {
-        let _guard = TEST_MUTEX.lock().unwrap();
-        cleanup_test_logs();
-
-        // Test format string variant
-        odebug!("Test value: {}", 42);
+        let dir = crate::test_dir("lib-default_variants");
+        crate::with_debug_dir(&dir, || {
+            // Test format string variant
+            odebug!("Test value: {}", 42);
 
-        // Test plain content variant
-        odebug!("Plain message");
+            // Test plain content variant
+            odebug!("Plain message");
 
-        // Test header and content variant (now using path syntax)
-        odebug!(::TestHeader("Test content"));
+            // Test header and content variant (now using path syntax)
+            odebug!(::TestHeader("Test content"));
+        });
 
         // Verify file was created
-        let path = crate::DEBUG_DIR.join("debug.log");
+        let path = dir.join("debug.log");
         assert!(Path::new(&path).exists(), "debug.log should exist");
 
         // Verify file content
@@ -456,17 +920,17 @@ mod tests {
 
     #[test]
     fn test_custom_filename_variants() {
-        let _guard = TEST_MUTEX.lock().unwrap();
-        cleanup_test_logs();
-
-        // Test all custom filename variants with the new syntax
-        odebug!(custom::("Test value: {}", 42));
-        odebug!(custom::("Plain message"));
-        odebug!(custom::TestHeader("Test content"));
-        odebug!("custom.log" => "Alternative content");
+        let dir = crate::test_dir("lib-custom_filename_variants");
+        crate::with_debug_dir(&dir, || {
+            // Test all custom filename variants with the new syntax
+            odebug!(custom::("Test value: {}", 42));
+            odebug!(custom::("Plain message"));
+            odebug!(custom::TestHeader("Test content"));
+            odebug!("custom.log" => "Alternative content");
+        });
 
         // Verify file was created
-        let path = crate::DEBUG_DIR.join("custom.log");
+        let path = dir.join("custom.log");
         assert!(Path::new(&path).exists(), "custom.log should exist");
 
         // Verify file content
@@ -490,16 +954,16 @@ mod tests {
 
     #[test]
     fn test_string_literal_filename_variants() {
-        let _guard = TEST_MUTEX.lock().unwrap();
-        cleanup_test_logs();
-
-        // Test string filename variants with => syntax
-        odebug!("test.log" => "Test value: {}", 42);
-        odebug!("test.log" => "Plain message");
-        odebug!("test.log" => "Test content");
+        let dir = crate::test_dir("lib-string_literal_filename_variants");
+        crate::with_debug_dir(&dir, || {
+            // Test string filename variants with => syntax
+            odebug!("test.log" => "Test value: {}", 42);
+            odebug!("test.log" => "Plain message");
+            odebug!("test.log" => "Test content");
+        });
 
         // Verify file was created
-        let path = crate::DEBUG_DIR.join("test.log");
+        let path = dir.join("test.log");
         assert!(Path::new(&path).exists(), "test.log should exist");
 
         // Verify file content
@@ -517,17 +981,17 @@ mod tests {
 
     #[test]
     fn test_literal_method_chaining() {
-        let _guard = TEST_MUTEX.lock().unwrap();
-        cleanup_test_logs();
-
-        // Test literal method chaining
-        odebug!("Message".to_file("chain.log"));
-        odebug!("Message".with_header("Test Header"));
-        odebug!("Message".to_file("chain.log").with_header("Combined"));
+        let dir = crate::test_dir("lib-literal_method_chaining");
+        crate::with_debug_dir(&dir, || {
+            // Test literal method chaining
+            odebug!("Message".to_file("chain.log"));
+            odebug!("Message".with_header("Test Header"));
+            odebug!("Message".to_file("chain.log").with_header("Combined"));
+        });
 
         // Verify files were created
-        let debug_path = crate::DEBUG_DIR.join("debug.log");
-        let chain_path = crate::DEBUG_DIR.join("chain.log");
+        let debug_path = dir.join("debug.log");
+        let chain_path = dir.join("chain.log");
 
         assert!(Path::new(&debug_path).exists(), "debug.log should exist");
         assert!(Path::new(&chain_path).exists(), "chain.log should exist");
@@ -552,21 +1016,21 @@ mod tests {
 
     #[test]
     fn test_identifier_method_chaining() {
-        let _guard = TEST_MUTEX.lock().unwrap();
-        cleanup_test_logs();
-
-        // Create variables to test identifier chaining
-        let message = "Variable message".to_string();
-        let header = "Variable header".to_string();
-
-        // Test identifier method chaining
-        odebug!(message.to_file("var.log"));
-        odebug!(message.with_header(header));
-        odebug!(message.to_file("var.log").with_header("Combined"));
+        let dir = crate::test_dir("lib-identifier_method_chaining");
+        crate::with_debug_dir(&dir, || {
+            // Create variables to test identifier chaining
+            let message = "Variable message".to_string();
+            let header = "Variable header".to_string();
+
+            // Test identifier method chaining
+            odebug!(message.to_file("var.log"));
+            odebug!(message.with_header(header));
+            odebug!(message.to_file("var.log").with_header("Combined"));
+        });
 
         // Verify files were created
-        let debug_path = crate::DEBUG_DIR.join("debug.log");
-        let var_path = crate::DEBUG_DIR.join("var.log");
+        let debug_path = dir.join("debug.log");
+        let var_path = dir.join("var.log");
 
         assert!(Path::new(&debug_path).exists(), "debug.log should exist");
         assert!(Path::new(&var_path).exists(), "var.log should exist");
@@ -588,11 +1052,181 @@ mod tests {
             "var.log should contain the combined header"
         );
     }
+
+    #[test]
+    fn test_compact_variant() {
+        let capture = crate::testing::capture();
+        odebug!(compact; tokens::Token("struct"));
+        odebug!(tokens::Token("Foo"));
+
+        let entries = capture.entries();
+        assert_eq!(entries[0].format, Some(crate::Format::Compact));
+        assert_eq!(entries[1].format, None);
+    }
+
+    #[test]
+    fn test_conditional_variants() {
+        let capture = crate::testing::capture();
+        let mut formatted = 0;
+        let mut count = || {
+            formatted += 1;
+            formatted
+        };
+
+        for name in ["Foo", "Bar"] {
+            odebug!(if name == "Foo"; derive::Expanded("{} {}", name, count()));
+            odebug_if!(name == "Bar", derive::Expanded("{} {}", name, count()));
+            odebug!(if name == "Baz"; compact; "{} {}", name, count());
+        }
+        let flag = true;
+        odebug!(if flag { "a" } else { "b" });
+        try_odebug!(if !flag { "a" } else { "b" }).unwrap();
+        odebug_tokens!(if flag { "c" } else { "d" }; n = 1);
+
+        let contents: Vec<String> = capture.entries().into_iter().map(|e| e.content).collect();
+        assert_eq!(contents, ["Foo 1", "Bar 2", "a", "b", "c"]);
+        assert_eq!(formatted, 2, "Content should only be formatted when logged");
+    }
+
+    #[test]
+    fn test_try_variants() {
+        let capture = crate::testing::capture();
+        let name = "Foo";
+
+        try_odebug!(derive::Expanded("impl for {}", name)).unwrap();
+        try_odebug!(if name == "Bar"; "skipped").unwrap();
+        try_odebug!(compact; "compact {}"; name = name).unwrap();
+
+        let file = format!("{}.rs", name);
+        let file = file.as_str();
+        let error = try_odebug!(file => "not a log file").unwrap_err();
+        assert_eq!(error.kind(), crate::ErrorKind::InvalidName);
+        assert_eq!(error.operation(), Some(crate::Operation::Validate));
+        assert_eq!(error.path(), Some(std::path::Path::new("Foo.rs")));
+
+        let entries = capture.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].format, Some(crate::Format::Compact));
+    }
+
+    #[test]
+    fn test_nested_path_variants() {
+        let capture = crate::testing::capture();
+        let attr = "derive";
+
+        odebug!(parse::attrs::Found("#[{}]", attr));
+        odebug!(parse::attrs::deep::Found(attr));
+        odebug!(expand::derive::("no header"));
+        odebug!("expand/derive.log" => "literal {}", attr);
+
+        let entries = capture.entries();
+        assert_eq!(entries[0].file, "parse/attrs.log");
+        assert_eq!(entries[0].header.as_deref(), Some("Found"));
+        assert_eq!(entries[0].content, "#[derive]");
+        assert_eq!(entries[1].file, "parse/attrs/deep.log");
+        assert_eq!(entries[2].file, "expand/derive.log");
+        assert_eq!(entries[2].header, None);
+        assert_eq!(entries[3].file, "expand/derive.log");
+    }
+
+    #[test]
+    fn test_fields() {
+        let capture = crate::testing::capture();
+        let name = String::from("Foo");
+        let variants = vec![1, 2, 3];
+
+        odebug!("expanded {}", name; ident = name, variants = variants.len());
+        odebug!(derive::Fields("sigils"); shown = %name, debugged = ?variants, ok = true,);
+        odebug!(if !name.is_empty(); compact; "plain");
+
+        let entries = capture.entries();
+        assert_eq!(entries[0].content, "expanded Foo");
+        assert_eq!(
+            entries[0].fields,
+            [
+                ("ident".to_string(), crate::Value::from("Foo")),
+                ("variants".to_string(), crate::Value::Int(3)),
+            ]
+        );
+        assert_eq!(entries[1].header.as_deref(), Some("Fields"));
+        assert_eq!(entries[1].field("shown"), Some(&crate::Value::from("Foo")));
+        assert_eq!(
+            entries[1].field("debugged"),
+            Some(&crate::Value::from("[1, 2, 3]"))
+        );
+        assert_eq!(entries[1].field("ok"), Some(&crate::Value::Bool(true)));
+        assert!(entries[2].fields.is_empty());
+        assert_eq!(capture.with_field("variants", "3").len(), 1);
+    }
+
+    #[test]
+    fn test_long_argument_lists() {
+        let capture = crate::testing::capture();
+
+        // the `;` before the fields at, and around, the end of a scanned chunk
+        odebug!("{}{}{}", 1, 2, 3; n = 7);
+        odebug!("{}{}{}{}", 1, 2, 3, 4; n = 9);
+        odebug!("{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}", 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69; n = 70);
+
+        let entries = capture.entries();
+        assert_eq!(entries[0].content, "123");
+        assert_eq!(entries[1].content, "1234");
+        assert_eq!(
+            entries[2].content,
+            (0..70).map(|i| i.to_string()).collect::<String>()
+        );
+        for entry in &entries {
+            assert!(entry.field("n").is_some());
+        }
+    }
+
+    #[test]
+    fn test_rate_limited_variants() {
+        let capture = crate::testing::capture();
+        for i in 0..10 {
+            odebug_once!(limits::Once("{}", i));
+            odebug_every!(4, limits::Every("{}", i));
+        }
+
+        assert_eq!(capture.with_header("Once").len(), 1);
+        let every: Vec<String> = capture
+            .with_header("Every")
+            .into_iter()
+            .map(|entry| entry.content)
+            .collect();
+        assert_eq!(every, ["0", "4", "8"]);
+
+        // call sites on the same line are told apart by their column
+        #[rustfmt::skip]
+        fn same_line(i: i32) -> u32 { odebug_once!(limits::Left("{}", i)); odebug_once!(limits::Right("{}", i)); line!() }
+        same_line(0);
+        let line = same_line(1);
+        let prefix = format!("{}:{}:", file!(), line);
+        let sites: Vec<_> = crate::callsite::suppressed()
+            .into_iter()
+            .filter(|(location, _)| location.starts_with(&prefix))
+            .collect();
+        assert_eq!(sites.len(), 2, "{:?}", sites);
+        assert_ne!(sites[0].0, sites[1].0);
+    }
+
+    #[test]
+    fn test_tokens_variant() {
+        let capture = crate::testing::capture();
+
+        odebug_tokens!(expand::Output("impl Foo {}"));
+
+        let entries = capture.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, crate::ContentKind::Rust);
+        assert_eq!(entries[0].header.as_deref(), Some("Output"));
+    }
 }
 
 #[cfg(test)]
 mod feature_tests {
     use super::*;
+    use once_cell::sync::Lazy;
     use std::sync::Mutex;
 
     static ENV_TEST_MUTEX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
@@ -617,7 +1251,7 @@ mod feature_tests {
             );
 
             assert!(
-                dir.file_name().map_or(false, |name| name == "odebug"),
+                dir.file_name().is_some_and(|name| name == "odebug"),
                 "Path should end with 'odebug' directory"
             );
         }
@@ -0,0 +1,410 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+
+use crate::error::{self, Error, ErrorKind, Operation};
+use crate::format::json_string;
+use crate::logger;
+
+/// The marker file recording the chosen [`Location`], written to the debug
+/// directory on first use.
+pub const LOCATION_FILE: &str = "location.json";
+
+static LOCATION: Lazy<Location> = Lazy::new(|| {
+    let configured = crate::determine_debug_dir();
+    resolve(candidates(&configured), &configured).unwrap_or_else(|error| {
+        error::record(&error);
+        logger::error_policy().handle(error);
+        Location::Stderr
+    })
+});
+
+/// The directories tried for the log files, in order of preference.
+fn candidates(configured: &Path) -> Vec<Location> {
+    let mut candidates = vec![Location::Configured(configured.to_path_buf())];
+    if let Some(target) = crate::find_target_dir() {
+        candidates.push(Location::Target(target.join("odebug")));
+    }
+    candidates.push(Location::Temp(std::env::temp_dir().join(format!(
+        "odebug-{:016x}",
+        fnv1a(crate::workspace_dir().to_string_lossy().as_bytes())
+    ))));
+    candidates
+}
+
+/// Where log files are written, see [`location`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// The directory chosen by the `output_to_target` and `use_workspace`
+    /// features
+    Configured(PathBuf),
+    /// `target/odebug`, because the configured directory was not writable
+    Target(PathBuf),
+    /// `odebug-<hash of the workspace>` in the temporary directory, because
+    /// neither of the above was writable
+    Temp(PathBuf),
+    /// No directory was writable, so the file output writes to stderr
+    Stderr,
+}
+
+impl Location {
+    /// Returns the directory log files are written to, or `None` for
+    /// [`Location::Stderr`].
+    pub fn dir(&self) -> Option<&Path> {
+        match self {
+            Location::Configured(dir) | Location::Target(dir) | Location::Temp(dir) => Some(dir),
+            Location::Stderr => None,
+        }
+    }
+
+    /// Names the variant, as recorded in [`LOCATION_FILE`] and the session
+    /// manifest.
+    pub(crate) fn strategy(&self) -> &'static str {
+        match self {
+            Location::Configured(_) => "configured",
+            Location::Target(_) => "target",
+            Location::Temp(_) => "temp",
+            Location::Stderr => "stderr",
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dir() {
+            Some(dir) => write!(f, "{} ({})", dir.display(), self.strategy()),
+            None => f.write_str("stderr"),
+        }
+    }
+}
+
+/// Returns where the log files of this process are written, unless
+/// overridden with [`set_debug_dir`] or per thread.
+///
+/// The configured directory is used if it can be created and written to.
+/// Read-only source trees and sandboxed builds fall back to
+/// `target/odebug`, then to a directory in the temporary directory that is
+/// unique to the workspace, and finally to stderr. The chosen directory
+/// records the choice in [`LOCATION_FILE`].
+///
+/// # Examples
+///
+/// ```
+/// if let Some(dir) = odebug::location().dir() {
+///     println!("logs are in {}", dir.display());
+/// }
+/// ```
+pub fn location() -> &'static Location {
+    &LOCATION
+}
+
+/// Finds where the log files of the workspace are, without creating any
+/// directory or writing [`LOCATION_FILE`] like [`location`] does.
+///
+/// Meant for tools that read the logs of another process, such as the
+/// `odebug` command line tool. Returns the location of this process if it
+/// was already resolved. Otherwise the candidates are checked in the order
+/// [`location`] tries them, and the first one holding a [`LOCATION_FILE`]
+/// is returned, as that is where a build wrote its logs. Without any, this
+/// is the first existing candidate, or else the configured directory, so
+/// the result is never [`Location::Stderr`].
+///
+/// # Examples
+///
+/// ```
+/// let location = odebug::find_location();
+/// println!("reading logs from {}", location);
+/// ```
+pub fn find_location() -> Location {
+    if let Some(location) = Lazy::get(&LOCATION) {
+        return location.clone();
+    }
+    find(candidates(&crate::determine_debug_dir()))
+}
+
+fn find(candidates: Vec<Location>) -> Location {
+    let exists = |marker: bool| {
+        candidates.iter().find(|candidate| match candidate.dir() {
+            Some(dir) if marker => dir.join(LOCATION_FILE).is_file(),
+            Some(dir) => dir.is_dir(),
+            None => false,
+        })
+    };
+    exists(true)
+        .or_else(|| exists(false))
+        .or(candidates.first())
+        .cloned()
+        .unwrap_or(Location::Stderr)
+}
+
+/// Returns the first candidate that can be created and written to, or the
+/// error of the first one if none can.
+fn resolve(candidates: Vec<Location>, configured: &Path) -> Result<Location, Error> {
+    let mut first_error = None;
+    let mut tried: Vec<PathBuf> = Vec::new();
+    for candidate in candidates {
+        let Some(dir) = candidate.dir() else {
+            continue;
+        };
+        if tried.iter().any(|t| t == dir) {
+            continue;
+        }
+        match write_marker(&candidate, configured) {
+            Ok(()) => return Ok(candidate),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            },
+        }
+        tried.push(dir.to_path_buf());
+    }
+    Err(first_error.unwrap_or_else(|| Error::new(ErrorKind::Directory)))
+}
+
+fn write_marker(location: &Location, configured: &Path) -> Result<(), Error> {
+    let dir = location.dir().unwrap_or(configured);
+    fs::create_dir_all(dir).map_err(|e| {
+        Error::new(ErrorKind::Directory)
+            .with_operation(Operation::CreateDirectory)
+            .with_path(dir)
+            .with_source(e)
+    })?;
+    let marker = format!(
+        "{{\"strategy\":{},\"dir\":{},\"configured\":{}}}\n",
+        json_string(location.strategy()),
+        json_string(&dir.to_string_lossy()),
+        json_string(&configured.to_string_lossy()),
+    );
+    let path = dir.join(LOCATION_FILE);
+    fs::write(&path, marker).map_err(|e| {
+        Error::from(e)
+            .with_operation(Operation::Write)
+            .with_path(path)
+    })
+}
+
+static GLOBAL_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+thread_local! {
+    static THREAD_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Returns the directory log files are written to.
+///
+/// That is the directory of the current thread if one was set with
+/// [`with_debug_dir`] or [`set_thread_debug_dir`], else the one set with
+/// [`set_debug_dir`], else the one of [`location`]. If no directory is
+/// writable, this is the configured one, even though entries go to stderr.
+///
+/// Unlike [`location`], this creates nothing: before the first entry is
+/// written, the directory of [`location`] is looked up with
+/// [`find_location`], so it may not exist yet.
+pub fn debug_dir() -> PathBuf {
+    match overridden() {
+        Some((dir, _)) => dir,
+        None => find_location()
+            .dir()
+            .map_or_else(crate::determine_debug_dir, Path::to_path_buf),
+    }
+}
+
+/// Returns the directory log files are written to and how it was chosen,
+/// or `None` if they go to stderr.
+pub(crate) fn current() -> Option<(PathBuf, &'static str)> {
+    if let Some(current) = overridden() {
+        return Some(current);
+    }
+    let location = location();
+    location
+        .dir()
+        .map(|dir| (dir.to_path_buf(), location.strategy()))
+}
+
+/// Returns the directory set for the current thread or globally, if any.
+fn overridden() -> Option<(PathBuf, &'static str)> {
+    if let Some(dir) = THREAD_DIR.with(|dir| dir.borrow().clone()) {
+        return Some((dir, "thread"));
+    }
+    if let Some(dir) = GLOBAL_DIR.read().ok().and_then(|dir| dir.clone()) {
+        return Some((dir, "global"));
+    }
+    None
+}
+
+/// Makes all threads write log files to `dir` from now on, unless they
+/// have a directory of their own. Unlike the configured directory, `dir`
+/// is used as is, without falling back if it is not writable.
+///
+/// Loggers built with [`LoggerBuilder::dir`](crate::LoggerBuilder::dir)
+/// keep writing to their own directory.
+pub fn set_debug_dir(dir: impl Into<PathBuf>) {
+    if let Ok(mut global) = GLOBAL_DIR.write() {
+        *global = Some(dir.into());
+    }
+}
+
+/// Undoes [`set_debug_dir`], going back to the directory of [`location`].
+pub fn reset_debug_dir() {
+    if let Ok(mut global) = GLOBAL_DIR.write() {
+        *global = None;
+    }
+}
+
+/// Makes the current thread write log files to `dir` from now on.
+pub fn set_thread_debug_dir(dir: impl Into<PathBuf>) {
+    THREAD_DIR.with(|thread| *thread.borrow_mut() = Some(dir.into()));
+}
+
+/// Undoes [`set_thread_debug_dir`] for the current thread.
+pub fn reset_thread_debug_dir() {
+    THREAD_DIR.with(|thread| *thread.borrow_mut() = None);
+}
+
+/// Runs `f` with the current thread writing log files to `dir`, restoring
+/// the previous directory afterwards, even if `f` panics.
+///
+/// This lets tests that run in parallel write to isolated directories.
+///
+/// # Examples
+///
+/// ```
+/// let dir = std::env::temp_dir().join("odebug-doc-with-debug-dir");
+/// odebug::with_debug_dir(&dir, || {
+///     odebug::odebug!("isolated");
+/// });
+/// # #[cfg(debug_assertions)]
+/// assert!(dir.join("debug.log").exists());
+/// ```
+pub fn with_debug_dir<R>(dir: impl Into<PathBuf>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<PathBuf>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            let _ = THREAD_DIR.try_with(|thread| *thread.borrow_mut() = previous);
+        }
+    }
+
+    let previous = THREAD_DIR.with(|thread| thread.borrow_mut().replace(dir.into()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// A stable hash, so that every process of a workspace picks the same
+/// temporary directory.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_falls_back_to_writable_candidate() {
+        let dir = crate::test_dir("location-fallback");
+        // a file where the directory should be makes it impossible to create
+        let blocked = dir.join("blocked");
+        fs::write(&blocked, "").unwrap();
+        let configured = blocked.join("odebug");
+        let candidates = vec![
+            Location::Configured(configured.clone()),
+            Location::Target(configured.clone()),
+            Location::Temp(dir.join("temp")),
+        ];
+
+        let location = resolve(candidates, &configured).unwrap();
+        assert_eq!(location, Location::Temp(dir.join("temp")));
+        let marker = fs::read_to_string(dir.join("temp").join(LOCATION_FILE)).unwrap();
+        assert!(marker.starts_with("{\"strategy\":\"temp\""), "{}", marker);
+    }
+
+    #[test]
+    fn test_reports_first_error_when_nothing_is_writable() {
+        let dir = crate::test_dir("location-nothing");
+        let blocked = dir.join("blocked");
+        fs::write(&blocked, "").unwrap();
+
+        let error = resolve(vec![Location::Configured(blocked.join("a"))], &blocked).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Directory);
+        assert_eq!(error.path(), Some(blocked.join("a").as_path()));
+    }
+
+    #[test]
+    fn test_find_prefers_marker_and_creates_nothing() {
+        let dir = crate::test_dir("location-find");
+        let configured = Location::Configured(dir.join("configured"));
+        let target = Location::Target(dir.join("target"));
+        let temp = Location::Temp(dir.join("temp"));
+        let candidates = || vec![configured.clone(), target.clone(), temp.clone()];
+
+        assert_eq!(find(candidates()), configured);
+        fs::create_dir(dir.join("temp")).unwrap();
+        assert_eq!(find(candidates()), temp);
+        fs::create_dir(dir.join("target")).unwrap();
+        fs::write(dir.join("temp").join(LOCATION_FILE), "{}").unwrap();
+        assert_eq!(find(candidates()), temp);
+
+        assert!(!dir.join("configured").exists());
+        assert!(!dir.join("target").join(LOCATION_FILE).exists());
+    }
+
+    #[test]
+    fn test_hash_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_debug_dir_overrides() {
+        let dir = crate::test_dir("location-overrides");
+        let outer = dir.join("outer");
+        let inner = dir.join("inner");
+
+        let other = std::thread::spawn(debug_dir);
+        let other_dir = other.join().unwrap();
+        set_thread_debug_dir(&outer);
+        assert_eq!(debug_dir(), outer);
+        with_debug_dir(&inner, || assert_eq!(debug_dir(), inner));
+        assert_eq!(debug_dir(), outer);
+
+        let panicked = std::panic::catch_unwind(|| with_debug_dir(&inner, || panic!("in scope")));
+        assert!(panicked.is_err());
+        assert_eq!(debug_dir(), outer);
+
+        reset_thread_debug_dir();
+        assert_ne!(debug_dir(), outer);
+        assert_eq!(debug_dir(), other_dir);
+
+        // the global directory is checked here only, as it applies to the
+        // tests running in parallel as well
+        let global = dir.join("global");
+        set_debug_dir(&global);
+        assert_eq!(debug_dir(), global);
+        assert_eq!(std::thread::spawn(debug_dir).join().unwrap(), global);
+        with_debug_dir(&inner, || assert_eq!(debug_dir(), inner));
+        set_thread_debug_dir(&outer);
+        assert_eq!(debug_dir(), outer);
+        reset_thread_debug_dir();
+        assert_eq!(debug_dir(), global);
+        reset_debug_dir();
+        assert_eq!(debug_dir(), other_dir);
+        assert!(!global.exists() && !inner.exists() && !outer.exists());
+    }
+
+    #[test]
+    fn test_with_debug_dir_redirects_output() {
+        let dir = crate::test_dir("location-redirect");
+        with_debug_dir(&dir, || {
+            crate::logger().log("redirected.log", None, "here").unwrap();
+        });
+        let content = fs::read_to_string(dir.join("redirected.log")).unwrap();
+        assert!(content.contains("here"), "{}", content);
+    }
+}
@@ -0,0 +1,208 @@
+//! Bridge between odebug and the [`log`] crate facade.
+//!
+//! [`OdebugLog`] is a [`log::Log`] implementation writing records into
+//! odebug files, and [`LogSink`] goes the other way, forwarding odebug
+//! entries to whatever `log` backend is installed. Both directions can be
+//! active at the same time; entries forwarded by a [`LogSink`] are not
+//! written back by [`OdebugLog`].
+
+use std::cell::Cell;
+use std::io;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::entry::Entry;
+use crate::sink::Sink;
+
+thread_local! {
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns the odebug file name for a `log` target, e.g. `my_crate.parser.log`
+/// for `my_crate::parser`.
+pub fn target_file(target: &str) -> String {
+    crate::entry::target_file(target)
+}
+
+/// A [`log::Log`] implementation writing records through the global odebug
+/// [`logger`](crate::logger()).
+///
+/// The record target selects the file (see [`target_file`]), the level
+/// becomes the header and the record's source location the context.
+///
+/// # Examples
+///
+/// ```
+/// use log::LevelFilter;
+/// use odebug::log_bridge::OdebugLog;
+///
+/// OdebugLog::new(LevelFilter::Debug).init().ok();
+/// log::debug!(target: "parser", "goes to parser.log");
+/// ```
+#[derive(Debug, Clone)]
+pub struct OdebugLog {
+    level: LevelFilter,
+    file: Option<String>,
+}
+
+impl OdebugLog {
+    /// Creates a bridge accepting records up to `level`.
+    pub fn new(level: LevelFilter) -> Self {
+        OdebugLog {
+            level,
+            file: None,
+        }
+    }
+
+    /// Writes every record to `file` instead of a per-target file.
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Installs the bridge as the global `log` logger and sets the maximum
+    /// level accordingly.
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for OdebugLog {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) || FORWARDING.with(Cell::get) {
+            return;
+        }
+
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => target_file(record.target()),
+        };
+        let mut entry =
+            Entry::new(file, record.args().to_string()).with_header(record.level().as_str());
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            entry = entry.with_context(format!("{}:{}", file, line));
+        }
+
+        let logger = crate::logger();
+        if let Err(e) = logger.log_entry(&entry) {
+            logger.handle_error(e);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = crate::logger().flush();
+    }
+}
+
+/// A [`Sink`] forwarding odebug entries to the installed `log` backend.
+///
+/// The target is `odebug::<file>` with the file extension stripped, and the
+/// message is the entry content, prefixed by its header if there is one.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use odebug::log_bridge::LogSink;
+/// use odebug::Logger;
+///
+/// let logger = Logger::builder().sink(LogSink::new(Level::Debug)).build();
+/// odebug::set_logger(logger).ok();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct LogSink {
+    level: Level,
+}
+
+impl LogSink {
+    /// Creates a sink forwarding entries at `level`.
+    pub fn new(level: Level) -> Self {
+        LogSink {
+            level,
+        }
+    }
+}
+
+impl Sink for LogSink {
+    fn write(&self, entry: &Entry, _rendered: &str) -> io::Result<()> {
+        let name = entry.file.strip_suffix(".log").unwrap_or(&entry.file);
+        let target = format!("odebug::{}", name);
+        let (file, line) = match entry.context.as_deref().and_then(|c| c.rsplit_once(':')) {
+            Some((file, line)) => (Some(file), line.parse().ok()),
+            None => (None, None),
+        };
+
+        FORWARDING.with(|forwarding| forwarding.set(true));
+        let logger = log::logger();
+        let record = |args| {
+            Record::builder()
+                .level(self.level)
+                .target(&target)
+                .file(file)
+                .line(line)
+                .args(args)
+                .build()
+        };
+        match &entry.header {
+            Some(header) => logger.log(&record(format_args!("{}: {}", header, entry.content))),
+            None => logger.log(&record(format_args!("{}", entry.content))),
+        }
+        FORWARDING.with(|forwarding| forwarding.set(false));
+
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        log::logger().flush();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_target_file() {
+        assert_eq!(target_file("parser"), "parser.log");
+        assert_eq!(target_file("my_crate::parser"), "my_crate.parser.log");
+    }
+
+    #[test]
+    fn test_log_record_becomes_entry() {
+        let capture = testing::capture();
+        let bridge = OdebugLog::new(LevelFilter::Debug);
+
+        bridge.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("my_crate::parser")
+                .file(Some("src/parser.rs"))
+                .line(Some(7))
+                .args(format_args!("unexpected {}", "token"))
+                .build(),
+        );
+        bridge.log(
+            &Record::builder()
+                .level(Level::Trace)
+                .target("my_crate::parser")
+                .args(format_args!("filtered out"))
+                .build(),
+        );
+
+        let entries = capture.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file, "my_crate.parser.log");
+        assert_eq!(entries[0].header.as_deref(), Some("WARN"));
+        assert_eq!(entries[0].context.as_deref(), Some("src/parser.rs:7"));
+        assert_eq!(entries[0].content, "unexpected token");
+    }
+}
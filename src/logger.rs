@@ -0,0 +1,549 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use once_cell::sync::OnceCell;
+
+use crate::entry::Entry;
+use crate::error::{self, Error, ErrorKind, ErrorPolicy, Operation};
+use crate::format::{Format, Formatter};
+use crate::name::validate_file_name;
+use crate::sink::{ConsoleSink, FileMode, FileSink, Sink};
+use crate::testing;
+
+type Filter = Box<dyn Fn(&Entry) -> bool + Send + Sync>;
+
+static LOGGER: OnceCell<Logger> = OnceCell::new();
+
+/// Environment variable listing files the default logger writes in
+/// [`Format::Compact`], separated by commas, e.g. `tokens.log,loop.log`.
+pub const COMPACT_ENV: &str = "ODEBUG_COMPACT";
+
+/// Returns the global logger used by [`odebug!`](crate::odebug) and
+/// [`write_to_debug_file`](crate::write_to_debug_file).
+///
+/// If no logger was installed with [`set_logger`], a default one writing to
+/// the [`debug_dir`](crate::debug_dir) is created on first use.
+pub fn logger() -> &'static Logger {
+    LOGGER.get_or_init(Logger::default)
+}
+
+/// Returns the error policy of the global logger, without creating it.
+pub(crate) fn error_policy() -> ErrorPolicy {
+    LOGGER.get().map(Logger::error_policy).unwrap_or_default()
+}
+
+/// Installs `logger` as the global logger.
+///
+/// This can only happen once, and only before anything has been logged
+/// through the global logger. Otherwise the logger is handed back in the
+/// `Err` variant.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::{FileMode, Logger};
+///
+/// let logger = Logger::builder().file_mode(FileMode::Append).build();
+/// odebug::set_logger(logger).ok();
+/// ```
+pub fn set_logger(logger: Logger) -> Result<(), Logger> {
+    LOGGER.set(logger)
+}
+
+/// A configured logging pipeline: filters, formatters and a set of sinks.
+///
+/// The macros use the global instance returned by [`logger()`], but a
+/// logger can just as well be constructed and used directly.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::{Format, Logger};
+///
+/// let logger = Logger::builder()
+///     .dir(std::env::temp_dir().join("odebug-doc"))
+///     .format(Format::Text)
+///     .filter(|entry| entry.file != "noisy.log")
+///     .build();
+///
+/// logger.log("debug.log", Some("INFO"), "Something happened").unwrap();
+/// ```
+pub struct Logger {
+    formatter: Box<dyn Formatter>,
+    file_formatters: HashMap<String, Box<dyn Formatter>>,
+    filters: Vec<Filter>,
+    sinks: Vec<Box<dyn Sink>>,
+    error_policy: ErrorPolicy,
+}
+
+impl Logger {
+    /// Returns a builder for a new logger.
+    pub fn builder() -> LoggerBuilder {
+        LoggerBuilder::default()
+    }
+
+    /// Logs `content` to `file` with an optional header.
+    pub fn log(&self, file: &str, header: Option<&str>, content: &str) -> Result<(), Error> {
+        let mut entry = Entry::new(file, content);
+        entry.header = header.map(str::to_string);
+        self.log_entry(&entry)
+    }
+
+    /// Logs a fully constructed [`Entry`].
+    ///
+    /// Fails with [`ErrorKind::InvalidName`](crate::ErrorKind::InvalidName)
+    /// if the file name of the entry is invalid, see [`validate_file_name`].
+    ///
+    /// The entry is rendered with the formatter registered for its file,
+    /// or the logger's formatter, unless it carries a format of its own.
+    ///
+    /// While a [`testing::capture`] is active on the current thread, entries
+    /// accepted by the filters go to the capture instead of the sinks.
+    ///
+    /// Every sink is written to even if an earlier one fails; the first
+    /// error encountered is returned. Failures are also recorded for
+    /// [`health`](crate::health), but not handled by the
+    /// [`ErrorPolicy`], see [`Logger::handle_error`].
+    pub fn log_entry(&self, entry: &Entry) -> Result<(), Error> {
+        let result = self.try_log_entry(entry);
+        if let Err(e) = &result {
+            error::record(e);
+        }
+        result
+    }
+
+    fn try_log_entry(&self, entry: &Entry) -> Result<(), Error> {
+        let sanitized;
+        let file = validate_file_name(&entry.file)
+            .map_err(|e| Error::invalid_name(e).with_path(&entry.file))?;
+        let entry = match file {
+            Cow::Borrowed(_) => entry,
+            Cow::Owned(file) => {
+                sanitized = Entry {
+                    file,
+                    ..entry.clone()
+                };
+                &sanitized
+            },
+        };
+
+        if !self.filters.iter().all(|filter| filter(entry)) {
+            return Ok(());
+        }
+
+        if testing::capture_entry(entry) {
+            return Ok(());
+        }
+
+        // the format of the entry also decides the extension of its file
+        let formatter: &dyn Formatter = match &entry.format {
+            Some(format) => format,
+            None => self
+                .file_formatters
+                .get(&entry.file)
+                .unwrap_or(&self.formatter)
+                .as_ref(),
+        };
+
+        let renamed;
+        let entry = match formatter.file_name(&entry.file) {
+            Cow::Borrowed(_) => entry,
+            Cow::Owned(file) => {
+                renamed = Entry {
+                    file,
+                    ..entry.clone()
+                };
+                &renamed
+            },
+        };
+
+        let mut buf = Vec::new();
+        formatter.format(entry, &mut buf).map_err(|e| {
+            Error::new(ErrorKind::Format)
+                .with_operation(Operation::Format)
+                .with_path(&entry.file)
+                .with_source(e)
+        })?;
+        let rendered = String::from_utf8_lossy(&buf);
+        let mut result = Ok(());
+        for sink in &self.sinks {
+            if let Err(e) = sink.write(entry, &rendered) {
+                if result.is_ok() {
+                    result = Err(Error::from(e).or_operation(Operation::Write));
+                }
+            }
+        }
+        result
+    }
+
+    /// Flushes all sinks of this logger.
+    pub fn flush(&self) -> Result<(), Error> {
+        for sink in &self.sinks {
+            sink.flush()
+                .map_err(|e| Error::from(e).or_operation(Operation::Flush))?;
+        }
+        Ok(())
+    }
+
+    /// Handles a failure according to the [`ErrorPolicy`] of this logger.
+    /// The macros call this for every entry that could not be logged.
+    pub fn handle_error(&self, error: Error) {
+        self.error_policy.handle(error);
+    }
+
+    /// Returns the [`ErrorPolicy`] of this logger.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+}
+
+impl Default for Logger {
+    /// The default logger, which also mirrors entries to stderr when
+    /// [`MIRROR_ENV`](crate::sink::MIRROR_ENV) is set, and writes the files
+    /// listed in [`COMPACT_ENV`] in [`Format::Compact`].
+    fn default() -> Self {
+        let mut builder = Logger::builder();
+        if let Ok(files) = std::env::var(COMPACT_ENV) {
+            for file in files.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+                builder = builder.file_formatter(file, Format::Compact);
+            }
+        }
+        match ConsoleSink::from_env() {
+            Some(console) => builder.sink(console).build(),
+            None => builder.build(),
+        }
+    }
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Logger")
+            .field("file_formatters", &self.file_formatters.len())
+            .field("filters", &self.filters.len())
+            .field("sinks", &self.sinks.len())
+            .field("error_policy", &self.error_policy)
+            .finish()
+    }
+}
+
+/// Builder for [`Logger`].
+///
+/// By default the built logger writes [`Format::Text`] entries to files in
+/// the [`debug_dir`](crate::debug_dir), clearing each file on its first
+/// write.
+pub struct LoggerBuilder {
+    dir: Option<PathBuf>,
+    formatter: Box<dyn Formatter>,
+    file_formatters: HashMap<String, Box<dyn Formatter>>,
+    file_mode: FileMode,
+    file_output: bool,
+    filters: Vec<Filter>,
+    sinks: Vec<Box<dyn Sink>>,
+    error_policy: ErrorPolicy,
+}
+
+impl Default for LoggerBuilder {
+    fn default() -> Self {
+        LoggerBuilder {
+            dir: None,
+            formatter: Box::new(Format::default()),
+            file_formatters: HashMap::new(),
+            file_mode: FileMode::default(),
+            file_output: true,
+            filters: Vec::new(),
+            sinks: Vec::new(),
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+}
+
+impl LoggerBuilder {
+    /// Sets the output directory of the file output.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the built-in format entries are rendered in.
+    pub fn format(self, format: Format) -> Self {
+        self.formatter(format)
+    }
+
+    /// Sets the formatter used for every file without a formatter of its
+    /// own.
+    pub fn formatter<F: Formatter + 'static>(mut self, formatter: F) -> Self {
+        self.formatter = Box::new(formatter);
+        self
+    }
+
+    /// Sets the formatter used for entries logged to `file`, e.g.
+    /// [`Format::Compact`] for a high-volume log.
+    pub fn file_formatter<F: Formatter + 'static>(
+        mut self,
+        file: impl Into<String>,
+        formatter: F,
+    ) -> Self {
+        self.file_formatters
+            .insert(file.into(), Box::new(formatter));
+        self
+    }
+
+    /// Sets the [`FileMode`] of the file output.
+    pub fn file_mode(mut self, mode: FileMode) -> Self {
+        self.file_mode = mode;
+        self
+    }
+
+    /// Enables or disables the file output. Enabled by default.
+    pub fn file_output(mut self, enabled: bool) -> Self {
+        self.file_output = enabled;
+        self
+    }
+
+    /// Adds a filter. Only entries accepted by every filter are logged.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Entry) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Sets what happens when logging from the macros fails. Defaults to
+    /// [`ErrorPolicy::EprintOnce`].
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Adds a sink in addition to the file output.
+    pub fn sink<S: Sink + 'static>(mut self, sink: S) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Builds the logger.
+    pub fn build(self) -> Logger {
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::with_capacity(self.sinks.len() + 1);
+        if self.file_output {
+            let file_sink = match self.dir {
+                Some(dir) => FileSink::in_dir(dir),
+                None => FileSink::new(),
+            };
+            sinks.push(Box::new(file_sink.mode(self.file_mode)));
+        }
+        sinks.extend(self.sinks);
+
+        Logger {
+            formatter: self.formatter,
+            file_formatters: self.file_formatters,
+            filters: self.filters,
+            sinks,
+            error_policy: self.error_policy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_logger_writes_to_configured_dir() {
+        let dir = crate::test_dir("logger-dir");
+        let logger = Logger::builder().dir(&dir).build();
+
+        logger
+            .log("out.log", Some("HEADER"), "Logged content")
+            .unwrap();
+
+        let content = fs::read_to_string(dir.join("out.log")).unwrap();
+        assert!(
+            content.contains("> HEADER"),
+            "Log should contain the header"
+        );
+        assert!(
+            content.contains("Logged content"),
+            "Log should contain the content"
+        );
+    }
+
+    #[test]
+    fn test_logger_filters() {
+        let dir = crate::test_dir("logger-filters");
+        let logger = Logger::builder()
+            .dir(&dir)
+            .filter(|entry| !entry.content.contains("skip"))
+            .build();
+
+        logger.log("out.log", None, "keep this").unwrap();
+        logger.log("out.log", None, "skip this").unwrap();
+
+        let content = fs::read_to_string(dir.join("out.log")).unwrap();
+        assert!(content.contains("keep this"));
+        assert!(
+            !content.contains("skip this"),
+            "Filtered entry should not be logged"
+        );
+    }
+
+    #[test]
+    fn test_logger_append_mode() {
+        let dir = crate::test_dir("logger-append");
+        fs::write(dir.join("out.log"), "previous run\n").unwrap();
+
+        let logger = Logger::builder()
+            .dir(&dir)
+            .file_mode(FileMode::Append)
+            .build();
+        logger.log("out.log", None, "this run").unwrap();
+
+        let content = fs::read_to_string(dir.join("out.log")).unwrap();
+        assert!(
+            content.contains("previous run"),
+            "Append mode should keep old content"
+        );
+        assert!(content.contains("this run"));
+    }
+
+    #[test]
+    fn test_logger_truncate_mode() {
+        let dir = crate::test_dir("logger-truncate");
+        fs::write(dir.join("out.log"), "previous run\n").unwrap();
+
+        let logger = Logger::builder().dir(&dir).build();
+        logger.log("out.log", None, "first").unwrap();
+        logger.log("out.log", None, "second").unwrap();
+
+        let content = fs::read_to_string(dir.join("out.log")).unwrap();
+        assert!(
+            !content.contains("previous run"),
+            "Truncate mode should clear old content"
+        );
+        assert!(content.contains("first") && content.contains("second"));
+    }
+
+    #[test]
+    fn test_logger_file_formatter() {
+        let dir = crate::test_dir("logger-formatters");
+        let logger = Logger::builder()
+            .dir(&dir)
+            .file_formatter("compact.log", Format::Compact)
+            .build();
+
+        logger
+            .log("compact.log", Some("HEADER"), "one\ntwo")
+            .unwrap();
+        logger.log("text.log", Some("HEADER"), "one\ntwo").unwrap();
+
+        let compact = fs::read_to_string(dir.join("compact.log")).unwrap();
+        assert_eq!(compact, "[HEADER] | one\\ntwo\n");
+        let text = fs::read_to_string(dir.join("text.log")).unwrap();
+        assert!(
+            text.contains("> HEADER"),
+            "Other files keep the text format"
+        );
+    }
+
+    #[test]
+    fn test_entry_format_overrides_file_formatter() {
+        let dir = crate::test_dir("logger-entry-format");
+        let logger = Logger::builder().dir(&dir).build();
+
+        let entry = Entry::new("mixed.log", "one line")
+            .with_context("src/lib.rs:7")
+            .with_format(Format::Compact);
+        logger.log_entry(&entry).unwrap();
+        logger.log("mixed.log", Some("HEADER"), "framed").unwrap();
+
+        let content = fs::read_to_string(dir.join("mixed.log")).unwrap();
+        assert!(
+            content.starts_with("src/lib.rs:7 | one line\n"),
+            "{}",
+            content
+        );
+        assert!(content.contains("> HEADER"), "{}", content);
+
+        // the file gets the extension of the entry's format
+        let json = Logger::builder().dir(&dir).format(Format::Json).build();
+        json.log("json.log", None, "json").unwrap();
+        json.log_entry(&Entry::new("json.log", "compact").with_format(Format::Compact))
+            .unwrap();
+        json.log_entry(&Entry::new("json.log", "markdown").with_format(Format::Markdown))
+            .unwrap();
+        let text = Logger::builder().dir(&dir).build();
+        text.log_entry(&Entry::new("other.log", "json").with_format(Format::Json))
+            .unwrap();
+
+        let read = |file: &str| -> Vec<String> {
+            crate::parse::read_file(dir.join(file))
+                .unwrap()
+                .map(|entry| entry.unwrap().content)
+                .collect()
+        };
+        assert_eq!(read("json.jsonl"), ["json"]);
+        assert_eq!(read("json.log"), ["compact"]);
+        assert_eq!(read("json.md"), ["markdown"]);
+        assert_eq!(read("other.jsonl"), ["json"]);
+        assert!(!dir.join("other.log").exists());
+    }
+
+    #[test]
+    fn test_logger_rejects_invalid_file_names() {
+        let dir = crate::test_dir("logger-names");
+        let logger = Logger::builder().dir(&dir).build();
+
+        let error = logger.log("../escape.log", None, "outside").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidName);
+        assert!(!dir.join("../escape.log").exists());
+
+        logger.log("no_extension", None, "inside").unwrap();
+        assert!(dir.join("no_extension.log").exists());
+    }
+
+    #[test]
+    fn test_logger_creates_subdirectories() {
+        let dir = crate::test_dir("logger-subdirs");
+        let logger = Logger::builder().dir(&dir).build();
+
+        logger.log("parse/attrs.log", None, "nested").unwrap();
+        logger.log("parse/deeper/more.log", None, "deeper").unwrap();
+
+        let content = fs::read_to_string(dir.join("parse").join("attrs.log")).unwrap();
+        assert!(content.contains("nested"));
+        assert!(dir.join("parse/deeper/more.log").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_logger_refuses_symlinked_subdirectory() {
+        let dir = crate::test_dir("logger-symlink");
+        let outside = crate::test_dir("logger-symlink-target");
+        std::os::unix::fs::symlink(&outside, dir.join("escape")).unwrap();
+
+        let logger = Logger::builder().dir(&dir).build();
+        let error = logger.log("escape/out.log", None, "outside").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Permission);
+        assert!(!outside.join("out.log").exists());
+
+        let error = logger.log("escape/a/b.log", None, "outside").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Permission);
+        assert!(!outside.join("a").exists());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid log file name `../escape.log`")]
+    fn test_panic_error_policy() {
+        let logger = Logger::builder()
+            .dir(crate::test_dir("logger-policy"))
+            .error_policy(ErrorPolicy::Panic)
+            .build();
+        let error = logger.log("../escape.log", None, "outside").unwrap_err();
+        assert!(crate::health().errors > 0);
+        logger.handle_error(error);
+    }
+}
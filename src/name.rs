@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+use std::io;
+
+/// Extensions log files may have. Names without an extension get `.log`
+/// appended.
+pub const ALLOWED_EXTENSIONS: &[&str] = &["log", "txt", "md", "jsonl"];
+
+/// Checks that `name` is a valid log file name, and appends `.log` if it
+/// has no extension.
+///
+/// Log file names are joined onto the debug directory, so they must stay
+/// inside of it. They may contain subdirectories separated by `/`, like
+/// `parse/attrs.log`, but empty names, absolute paths, `\\` separators, `.`,
+/// `..` and empty path components, characters that are illegal in file
+/// names on some platform and extensions other than [`ALLOWED_EXTENSIONS`]
+/// are rejected with an error of kind [`io::ErrorKind::InvalidInput`].
+///
+/// String literals passed to the macros are checked at compile time:
+///
+/// ```compile_fail
+/// odebug::odebug!("../../src/lib.rs" => "overwritten");
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use odebug::validate_file_name;
+///
+/// assert_eq!(validate_file_name("expand.log").unwrap(), "expand.log");
+/// assert_eq!(validate_file_name("expand").unwrap(), "expand.log");
+///
+/// let error = validate_file_name("../../src/lib.rs").unwrap_err();
+/// assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+/// ```
+pub fn validate_file_name(name: &str) -> io::Result<Cow<'_, str>> {
+    if let Some(problem) = file_name_problem(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid log file name `{}`: {}", name, problem),
+        ));
+    }
+    let bytes = name.as_bytes();
+    Ok(match extension_start(bytes, last_component(bytes)) {
+        Some(_) => Cow::Borrowed(name),
+        None => Cow::Owned(format!("{}.log", name)),
+    })
+}
+
+/// Fails compilation of a macro call with an invalid literal file name.
+#[doc(hidden)]
+pub const fn check_file_name(name: &str) {
+    if let Some(problem) = file_name_problem(name) {
+        panic!("{}", problem);
+    }
+}
+
+/// Returns what is wrong with `name`, if anything.
+const fn file_name_problem(name: &str) -> Option<&'static str> {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() {
+        return Some("log file names must not be empty");
+    }
+    if bytes[0] == b'/' || bytes[0] == b'\\' {
+        return Some("log file names must not be absolute paths");
+    }
+
+    let mut i = 0;
+    let mut component = 0;
+    while i <= bytes.len() {
+        if i == bytes.len() || bytes[i] == b'/' {
+            match component_problem(bytes, component, i) {
+                Some(problem) => return Some(problem),
+                None => component = i + 1,
+            }
+            i += 1;
+            continue;
+        }
+        match bytes[i] {
+            b'\\' => return Some("log file names must separate directories with `/`"),
+            b'<' | b'>' | b':' | b'"' | b'|' | b'?' | b'*' => {
+                return Some("log file names must not contain any of `<>:\"|?*`")
+            },
+            0..=0x1f | 0x7f => return Some("log file names must not contain control characters"),
+            _ => {},
+        }
+        i += 1;
+    }
+
+    let file = last_component(bytes);
+    match extension_start(bytes, file) {
+        Some(start) if !is_allowed_extension(bytes, start) => {
+            Some("log file extensions must be one of `log`, `txt`, `md` or `jsonl`")
+        },
+        _ => None,
+    }
+}
+
+/// Checks the path component `bytes[start..end]`.
+const fn component_problem(bytes: &[u8], start: usize, end: usize) -> Option<&'static str> {
+    match end - start {
+        0 => Some("log file names must not contain empty path components"),
+        1 if bytes[start] == b'.' => Some("log file names must not contain `.` components"),
+        2 if bytes[start] == b'.' && bytes[start + 1] == b'.' => {
+            Some("log file names must not contain `..` components")
+        },
+        _ => None,
+    }
+}
+
+/// Returns the index the last path component of `bytes` starts at.
+const fn last_component(bytes: &[u8]) -> usize {
+    let mut i = bytes.len();
+    while i > 0 {
+        if bytes[i - 1] == b'/' {
+            return i;
+        }
+        i -= 1;
+    }
+    0
+}
+
+/// Returns the index after the last `.` of the file name starting at
+/// `file`, unless the name starts with its only dot, like `.hidden`.
+const fn extension_start(bytes: &[u8], file: usize) -> Option<usize> {
+    let mut i = bytes.len();
+    while i > file + 1 {
+        i -= 1;
+        if bytes[i] == b'.' {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+const fn is_allowed_extension(bytes: &[u8], start: usize) -> bool {
+    let mut e = 0;
+    'extensions: while e < ALLOWED_EXTENSIONS.len() {
+        let allowed = ALLOWED_EXTENSIONS[e].as_bytes();
+        e += 1;
+        if bytes.len() - start != allowed.len() {
+            continue;
+        }
+        let mut i = 0;
+        while i < allowed.len() {
+            if bytes[start + i] != allowed[i] {
+                continue 'extensions;
+            }
+            i += 1;
+        }
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        for name in [
+            "debug.log",
+            "my_crate.parser.log",
+            "notes.txt",
+            "expand.md",
+            ".hidden.log",
+            "parse/attrs.log",
+            "v1.2/deep/er.log",
+        ] {
+            assert_eq!(validate_file_name(name).unwrap(), name);
+        }
+        assert_eq!(validate_file_name("expand").unwrap(), "expand.log");
+        assert_eq!(
+            validate_file_name("v1.2/expand").unwrap(),
+            "v1.2/expand.log"
+        );
+        assert_eq!(validate_file_name(".hidden").unwrap(), ".hidden.log");
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for name in [
+            "",
+            "/etc/passwd.log",
+            "../../src/lib.rs",
+            "..",
+            "sub/../../escape.log",
+            "sub/./dir.log",
+            "sub//dir.log",
+            "sub/",
+            "sub\\dir.log",
+            "C:\\debug.log",
+            "what?.log",
+            "new\nline.log",
+            "lib.rs",
+            "trailing.",
+        ] {
+            let error = validate_file_name(name).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{:?}", name);
+        }
+    }
+}
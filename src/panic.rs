@@ -0,0 +1,132 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::Write as _;
+use std::panic;
+use std::thread;
+
+use once_cell::sync::OnceCell;
+
+use crate::entry::Entry;
+
+/// The file panics are logged to by [`install_panic_hook`].
+pub const PANIC_FILE: &str = "panic.log";
+
+static PANIC_LOG: OnceCell<String> = OnceCell::new();
+
+/// Returns the file panics are logged to, if the hook is installed.
+pub(crate) fn panic_file() -> Option<&'static str> {
+    PANIC_LOG.get().map(String::as_str)
+}
+
+/// Installs a panic hook logging every panic to [`PANIC_FILE`].
+///
+/// See [`install_panic_hook_to`].
+pub fn install_panic_hook() {
+    install_panic_hook_to(PANIC_FILE);
+}
+
+/// Installs a panic hook logging every panic to `file`.
+///
+/// The entry holds the panic message, the thread and, when enabled through
+/// `RUST_BACKTRACE`, a backtrace, with the panic location as its context.
+/// All sinks of the global logger are flushed afterwards, and the hook
+/// that was installed before is called, so the usual output on stderr is
+/// kept.
+///
+/// This is most useful at the start of a proc-macro, where a panic only
+/// shows up as "proc-macro derive panicked" in the compiler output.
+/// The hook is installed only once per process; later calls do nothing.
+///
+/// # Examples
+///
+/// ```
+/// odebug::install_panic_hook_to("derive.log");
+/// ```
+pub fn install_panic_hook_to(file: impl Into<String>) {
+    let mut installed = false;
+    PANIC_LOG.get_or_init(|| {
+        installed = true;
+        file.into()
+    });
+    if !installed {
+        return;
+    }
+
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(file) = PANIC_LOG.get() {
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message,
+                None => match info.payload().downcast_ref::<String>() {
+                    Some(message) => message.as_str(),
+                    None => "Box<dyn Any>",
+                },
+            };
+            let mut entry = Entry::new(file.as_str(), panic_content(message)).with_header("PANIC");
+            if let Some(location) = info.location() {
+                entry = entry.with_context(format!("{}:{}", location.file(), location.line()));
+            }
+
+            let logger = crate::logger();
+            let _ = logger.log_entry(&entry);
+            let _ = logger.flush();
+        }
+        previous(info);
+    }));
+}
+
+fn panic_content(message: &str) -> String {
+    let mut content = message.to_string();
+    let _ = write!(
+        content,
+        "\n\nthread: {}",
+        thread::current().name().unwrap_or("<unnamed>")
+    );
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        let _ = write!(content, "\n\nbacktrace:\n{}", backtrace);
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_panic_is_logged() {
+        let dir = crate::test_dir("panic");
+        install_panic_hook_to("panic-test.log");
+        let capture = testing::capture();
+
+        let thread_dir = dir.clone();
+        let result = thread::Builder::new()
+            .name("panicking".to_string())
+            .spawn(|| crate::with_debug_dir(thread_dir, || panic!("something broke: {}", 42)))
+            .unwrap()
+            .join();
+        assert!(result.is_err());
+        // the panic happened on another thread, so it bypassed the capture
+        assert!(capture.is_empty());
+
+        let result = panic::catch_unwind(|| panic!("on this thread"));
+        // the hook applies to the whole process, so the panics of tests
+        // running after this one must not be logged
+        let _ = panic::take_hook();
+        assert!(result.is_err());
+
+        let logged = std::fs::read_to_string(dir.join("panic-test.log")).unwrap();
+        assert!(logged.contains("something broke: 42"), "{}", logged);
+        assert!(logged.contains("thread: panicking"), "{}", logged);
+
+        let entries = capture.entries();
+        assert_eq!(entries.len(), 1, "{}", capture.__describe());
+        assert_eq!(entries[0].file, "panic-test.log");
+        assert_eq!(entries[0].header.as_deref(), Some("PANIC"));
+        assert!(entries[0].content.starts_with("on this thread\n\nthread: "));
+        assert!(entries[0]
+            .context
+            .as_deref()
+            .is_some_and(|context| context.starts_with("src/panic.rs:")));
+    }
+}
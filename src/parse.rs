@@ -0,0 +1,811 @@
+//! Reads the text layout written by odebug back into structured entries.
+//!
+//! An entry starts at a blank line followed by a separator line, a
+//! `> header (context)` line, any `>   key = value` field lines and another
+//! separator line. Field values are read back as the first type their text
+//! fits, see [`Value::infer`](crate::Value::infer). Everything up to
+//! the next such block is the entry's content, so multi-line content and
+//! content containing lone separator lines are read back intact.
+//!
+//! Entries written without a header or context have no framing at all, and
+//! are indistinguishable from the content of the entry before them. They
+//! are only recovered as separate entries at the start of a file. Content
+//! that itself contains a complete header block is ambiguous in the same
+//! way. Logs written with [`Format::Framed`](crate::Format::Framed) have
+//! neither problem: each entry is read up to its closing fence, whatever
+//! the content looks like. Both layouts may be mixed within one file.
+//!
+//! Lines written with [`Format::Compact`](crate::Format::Compact) are read
+//! back at the start of a file and after other compact lines or fenced
+//! entries. After the content of a text entry they are indistinguishable
+//! from more content. Files with an `.md` extension are read as written
+//! by [`Format::Markdown`](crate::Format::Markdown), and files with a
+//! `.jsonl` extension as written by [`Format::Json`](crate::Format::Json).
+//!
+//! # Examples
+//!
+//! ```
+//! use odebug::{parse, Entry, Format};
+//!
+//! let entry = Entry::new("debug.log", "first line\nsecond line")
+//!     .with_header("INFO")
+//!     .with_context("src/lib.rs:42");
+//! let text = Format::Text.render(&entry);
+//!
+//! let parsed: Vec<Entry> = parse::parse_str(&text, "debug.log");
+//! assert_eq!(parsed, vec![entry]);
+//! ```
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Lines, Read};
+use std::path::{Path, PathBuf};
+
+use crate::entry::{ContentKind, Entry, Value};
+use crate::format::{is_fence, FIELD_PREFIX};
+use crate::SEPARATOR_LINE;
+
+/// The most field lines looked ahead for when detecting a header block.
+const MAX_FIELDS: usize = 256;
+
+/// Returns an iterator over the entries read from `reader`, attributing
+/// them to `file`. The extension of `file` selects the layout, see the
+/// [module documentation](self).
+pub fn entries<R: Read>(reader: R, file: impl Into<String>) -> Entries<BufReader<R>> {
+    let file = file.into();
+    let layout = match file.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("md") => Layout::Markdown,
+        Some("jsonl") => Layout::Json,
+        _ => Layout::Text,
+    };
+    Entries {
+        file,
+        layout,
+        lines: BufReader::new(reader).lines(),
+        buf: VecDeque::new(),
+        eof: false,
+    }
+}
+
+/// Opens the log file at `path` and returns an iterator over its entries.
+pub fn read_file(path: impl AsRef<Path>) -> io::Result<Entries<BufReader<File>>> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(entries(file, name))
+}
+
+/// Returns the log files in `dir` and its subdirectories, sorted by path.
+///
+/// HTML reports and JSON files such as the session manifest, which are
+/// not logs, are left out.
+pub fn log_files(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(&path, files)?;
+            } else if path
+                .extension()
+                .map_or(true, |ext| !matches!(ext.to_str(), Some("html" | "json")))
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(dir.as_ref(), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Parses all entries from `text`, attributing them to `file`.
+pub fn parse_str(text: &str, file: impl Into<String>) -> Vec<Entry> {
+    // reading from a byte slice cannot fail, but JSON lines may be malformed
+    entries(text.as_bytes(), file)
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Splits a `> header (context)` line into its header and context.
+fn parse_title(title: &str) -> (Option<String>, Option<String>) {
+    if title.is_empty() {
+        return (None, None);
+    }
+    if let Some(context) = title
+        .strip_prefix("[at ")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return (None, Some(context.to_string()));
+    }
+    if let Some((header, context)) = title
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    {
+        return (Some(header.to_string()), Some(context.to_string()));
+    }
+    (Some(title.to_string()), None)
+}
+
+/// Splits a `>   key = value` field line into its key and value.
+fn parse_field(line: &str) -> Option<(String, Value)> {
+    let (key, value) = line.strip_prefix(FIELD_PREFIX)?.split_once(" = ")?;
+    Some((key.trim_end().to_string(), Value::infer(value)))
+}
+
+/// Iterator over the entries of a log, created with [`entries`] or
+/// [`read_file`].
+#[derive(Debug)]
+pub struct Entries<B> {
+    file: String,
+    layout: Layout,
+    lines: Lines<B>,
+    buf: VecDeque<String>,
+    eof: bool,
+}
+
+impl<B: BufRead> Entries<B> {
+    /// Makes sure at least `n` lines are buffered, unless the input ends
+    /// first.
+    fn fill(&mut self, n: usize) -> io::Result<()> {
+        while !self.eof && self.buf.len() < n {
+            match self.lines.next() {
+                Some(line) => self.buf.push_back(line?),
+                None => self.eof = true,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of lines of the entry header block starting at
+    /// the front of the buffer, if one does.
+    fn entry_start(&mut self) -> io::Result<Option<usize>> {
+        self.fill(4)?;
+        if self.buf.len() < 4
+            || !self.buf[0].is_empty()
+            || self.buf[1] != SEPARATOR_LINE
+            || !self.buf[2].starts_with("> ")
+        {
+            return Ok(None);
+        }
+        // field lines between the title and the closing separator
+        let mut n = 3;
+        loop {
+            self.fill(n + 1)?;
+            match self.buf.get(n) {
+                Some(line) if line == SEPARATOR_LINE => return Ok(Some(n + 1)),
+                Some(line) if line.starts_with(FIELD_PREFIX) && n < MAX_FIELDS + 3 => n += 1,
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    /// Returns `true` if a fenced entry starts at the front of the buffer.
+    fn at_fenced_start(&mut self) -> io::Result<bool> {
+        self.fill(2)?;
+        Ok(self.buf.len() >= 2 && self.buf[0].is_empty() && is_fence(&self.buf[1]))
+    }
+
+    /// Reads a fenced entry, with the opening fence at the front of the
+    /// buffer.
+    fn fenced_entry(&mut self) -> io::Result<Entry> {
+        let fence = self.buf.drain(..2).nth(1).unwrap_or_default();
+        self.fill(1)?;
+        let title = self.buf.pop_front().unwrap_or_default();
+        let (header, context) = parse_title(title.trim_start_matches('>').trim_start());
+
+        let mut fields = Vec::new();
+        loop {
+            self.fill(1)?;
+            match self.buf.front().and_then(|line| parse_field(line)) {
+                Some(field) => {
+                    fields.push(field);
+                    self.buf.pop_front();
+                },
+                None => break,
+            }
+        }
+        // the end of the header block, missing in logs of older versions
+        if self.buf.front().is_some_and(|line| line == ">") {
+            self.buf.pop_front();
+        }
+
+        let mut lines = Vec::new();
+        loop {
+            self.fill(1)?;
+            match self.buf.pop_front() {
+                Some(line) if line == fence => break,
+                Some(line) => lines.push(line),
+                // the entry was cut off, e.g. by a crash
+                None => break,
+            }
+        }
+
+        let mut entry = Entry::new(self.file.clone(), lines.join("\n")).with_fields(fields);
+        entry.header = header;
+        entry.context = context;
+        Ok(entry)
+    }
+
+    /// Collects lines up to the next entry header block or the end of
+    /// input.
+    fn content(&mut self) -> io::Result<String> {
+        let mut lines = Vec::new();
+        loop {
+            self.fill(1)?;
+            if self.buf.is_empty() || self.entry_start()?.is_some() || self.at_fenced_start()? {
+                break;
+            }
+            lines.extend(self.buf.pop_front());
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn next_entry(&mut self) -> io::Result<Option<Entry>> {
+        loop {
+            self.fill(1)?;
+            if self.buf.is_empty() {
+                return Ok(None);
+            }
+
+            if self.at_fenced_start()? {
+                return self.fenced_entry().map(Some);
+            }
+
+            if let Some(len) = self.entry_start()? {
+                let mut block: Vec<String> = self.buf.drain(..len).collect();
+                let (header, context) = parse_title(&block[2][2..]);
+                block.pop();
+                let fields = block[3..].iter().filter_map(|line| parse_field(line));
+                let mut entry =
+                    Entry::new(self.file.clone(), self.content()?).with_fields(fields.collect());
+                entry.header = header;
+                entry.context = context;
+                return Ok(Some(entry));
+            }
+
+            // text entries start with a blank line, compact ones never do
+            if !self.buf[0].is_empty() {
+                if let Some(entry) = parse_compact(&self.buf[0], &self.file) {
+                    self.buf.pop_front();
+                    return Ok(Some(entry));
+                }
+            }
+
+            // unframed content, written without header and context
+            if self.buf[0].is_empty() {
+                self.buf.pop_front();
+            }
+            let content = self.content()?;
+            if !content.trim().is_empty() {
+                return Ok(Some(Entry::new(self.file.clone(), content)));
+            }
+        }
+    }
+}
+
+impl<B: BufRead> Entries<B> {
+    /// Reads the next entry of a [`Format::Markdown`](crate::Format::Markdown)
+    /// log: an optional `###` title, an optional field table and a fenced
+    /// code block.
+    fn next_markdown_entry(&mut self) -> io::Result<Option<Entry>> {
+        let mut entry = Entry::new(self.file.clone(), "");
+        loop {
+            self.fill(1)?;
+            let Some(line) = self.buf.pop_front() else {
+                return Ok(None);
+            };
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(title) = line.strip_prefix("### ") {
+                (entry.header, entry.context) = parse_markdown_title(title);
+                continue;
+            }
+            if line == "| field | value |" {
+                self.fill(1)?;
+                if self.buf.front().is_some_and(|line| line == "| --- | --- |") {
+                    self.buf.pop_front();
+                }
+                loop {
+                    self.fill(1)?;
+                    match self.buf.front().and_then(|line| parse_table_row(line)) {
+                        Some(field) => {
+                            entry.fields.push(field);
+                            self.buf.pop_front();
+                        },
+                        None => break,
+                    }
+                }
+                continue;
+            }
+
+            let fence_len = line.bytes().take_while(|&b| b == b'`').count();
+            if fence_len < 3 {
+                // not written by odebug, keep it as content of its own
+                entry.content = line;
+                return Ok(Some(entry));
+            }
+            if &line[fence_len..] == "rust" {
+                entry.kind = ContentKind::Rust;
+            }
+            let fence = &line[..fence_len];
+            let mut lines = Vec::new();
+            loop {
+                self.fill(1)?;
+                match self.buf.pop_front() {
+                    Some(line) if line == fence => break,
+                    Some(line) => lines.push(line),
+                    // the entry was cut off, e.g. by a crash
+                    None => break,
+                }
+            }
+            entry.content = lines.join("\n");
+            return Ok(Some(entry));
+        }
+    }
+
+    /// Reads the next line of a [`Format::Json`](crate::Format::Json) log.
+    fn next_json_entry(&mut self) -> io::Result<Option<Entry>> {
+        loop {
+            self.fill(1)?;
+            let Some(line) = self.buf.pop_front() else {
+                return Ok(None);
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return parse_json_entry(&line, &self.file)
+                .map(Some)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: not a JSON log entry: {}", self.file, line),
+                    )
+                });
+        }
+    }
+}
+
+/// Layout of a log file, selected by its extension.
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// [`Format::Text`](crate::Format::Text), possibly mixed with framed
+    /// and compact entries
+    Text,
+    Markdown,
+    Json,
+}
+
+/// Reverts the escaping of line breaks and backslashes done for compact
+/// lines and field values.
+fn unescape_line(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => unescaped.push('\n'),
+            ('\\', Some('r')) => unescaped.push('\r'),
+            ('\\', Some(c @ ('\\' | '|' | ']'))) => unescaped.push(c),
+            _ => {
+                unescaped.push(c);
+                continue;
+            },
+        }
+        chars.next();
+    }
+    unescaped
+}
+
+/// Parses a `[header] context key=value | content` line written by
+/// [`Format::Compact`](crate::Format::Compact).
+fn parse_compact(line: &str, file: &str) -> Option<Entry> {
+    let (mut meta, content) = match line.strip_prefix("| ") {
+        Some(content) => ("", content),
+        None => line.split_once(" | ")?,
+    };
+    let mut entry = Entry::new(file, unescape_line(content));
+    if let Some(rest) = meta.strip_prefix('[') {
+        let (header, rest) = split_unescaped(rest, ']')?;
+        entry.header = Some(unescape_line(header));
+        meta = rest;
+    }
+
+    let is_key = |key: &str| {
+        key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    for token in meta.split(' ').filter(|token| !token.is_empty()) {
+        match token.split_once('=') {
+            Some((key, value)) if is_key(key) => fields.push((key, value.to_string())),
+            _ if fields.is_empty() && entry.context.is_none() => {
+                entry.context = Some(unescape_line(token))
+            },
+            // a value containing spaces
+            _ => {
+                let (_, value) = fields.last_mut()?;
+                value.push(' ');
+                value.push_str(token);
+            },
+        }
+    }
+    entry.fields = fields
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), Value::infer(&unescape_line(&value))))
+        .collect();
+    Some(entry)
+}
+
+/// Splits `text` at the first `delimiter` not escaped with a backslash.
+fn split_unescaped(text: &str, delimiter: char) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == delimiter => return Some((&text[..i], &text[i + c.len_utf8()..])),
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Splits a Markdown `###` title into its header and context.
+fn parse_markdown_title(title: &str) -> (Option<String>, Option<String>) {
+    let context = |text: &str| {
+        let link = text
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|rest| rest.rsplit_once("]("))
+            .map(|(context, _)| context);
+        link.or_else(|| text.strip_prefix('`')?.strip_suffix('`'))
+            .map(str::to_string)
+    };
+    if let Some(context) = context(title) {
+        return (None, Some(context));
+    }
+    match title.rsplit_once(" — ") {
+        Some((header, rest)) if context(rest).is_some() => {
+            (Some(header.to_string()), context(rest))
+        },
+        _ => (Some(title.to_string()), None),
+    }
+}
+
+/// Parses a `| key | value |` row of a Markdown field table.
+fn parse_table_row(line: &str) -> Option<(String, Value)> {
+    let row = line.strip_prefix("| ")?.strip_suffix(" |")?;
+    let (key, value) = row.split_once(" | ")?;
+    let value = unescape_line(&value.replace("\\|", "|"));
+    Some((key.to_string(), Value::infer(&value)))
+}
+
+/// Parses a line written by [`Format::Json`](crate::Format::Json).
+fn parse_json_entry(line: &str, file: &str) -> Option<Entry> {
+    let mut parser = JsonParser {
+        chars: line.chars().peekable(),
+    };
+    let Json::Object(members) = parser.value()? else {
+        return None;
+    };
+    let mut entry = Entry::new(file, "");
+    for (key, value) in members {
+        match (key.as_str(), value) {
+            ("header", Json::Str(header)) => entry.header = Some(header),
+            ("context", Json::Str(context)) => entry.context = Some(context),
+            ("content", Json::Str(content)) => entry.content = content,
+            ("kind", Json::Str(kind)) if kind == "rust" => entry.kind = ContentKind::Rust,
+            ("fields", Json::Object(fields)) => {
+                for (key, value) in fields {
+                    let value = match value {
+                        Json::Bool(value) => Value::Bool(value),
+                        Json::Number(number) => Value::infer(&number),
+                        Json::Str(text) => Value::Str(text),
+                        // non-finite floats are written as null
+                        Json::Null => Value::Float(f64::NAN),
+                        Json::Object(_) => return None,
+                    };
+                    entry.fields.push((key, value));
+                }
+            },
+            _ => {},
+        }
+    }
+    Some(entry)
+}
+
+/// The subset of JSON written by [`Format::Json`](crate::Format::Json).
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    Str(String),
+    Object(Vec<(String, Json)>),
+}
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match *self.chars.peek()? {
+            '{' => self.object(),
+            '"' => self.string().map(Json::Str),
+            't' => self.literal("true", Json::Bool(true)),
+            'f' => self.literal("false", Json::Bool(false)),
+            'n' => self.literal("null", Json::Null),
+            _ => {
+                let mut number = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    number.push(c);
+                }
+                (!number.is_empty()).then_some(Json::Number(number))
+            },
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Option<Json> {
+        word.chars()
+            .all(|c| self.chars.next() == Some(c))
+            .then_some(value)
+    }
+
+    fn object(&mut self) -> Option<Json> {
+        self.chars.next();
+        let mut members = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek()? {
+                '}' => {
+                    self.chars.next();
+                    return Some(Json::Object(members));
+                },
+                ',' if !members.is_empty() => {
+                    self.chars.next();
+                    self.skip_whitespace();
+                },
+                _ => {},
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.chars.next()? != ':' {
+                return None;
+            }
+            members.push((key, self.value()?));
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.chars.next()? != '"' {
+            return None;
+        }
+        let mut text = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(text),
+                '\\' => match self.chars.next()? {
+                    'n' => text.push('\n'),
+                    'r' => text.push('\r'),
+                    't' => text.push('\t'),
+                    'b' => text.push('\u{8}'),
+                    'f' => text.push('\u{c}'),
+                    'u' => {
+                        let mut code = self.hex4()?;
+                        // a surrogate pair
+                        if (0xd800..0xdc00).contains(&code) {
+                            if self.chars.next()? != '\\' || self.chars.next()? != 'u' {
+                                return None;
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (self.hex4()? - 0xdc00);
+                        }
+                        text.push(char::from_u32(code)?);
+                    },
+                    c => text.push(c),
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        (0..4).try_fold(0, |code, _| {
+            Some(code * 16 + self.chars.next()?.to_digit(16)?)
+        })
+    }
+}
+
+impl<B: BufRead> Iterator for Entries<B> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.layout {
+            Layout::Text => self.next_entry(),
+            Layout::Markdown => self.next_markdown_entry(),
+            Layout::Json => self.next_json_entry(),
+        }
+        .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    fn render_all(entries: &[Entry]) -> String {
+        entries
+            .iter()
+            .map(|entry| Format::Text.render(entry))
+            .collect()
+    }
+
+    fn entries_of(text: &str, file: &str) -> io::Result<Vec<Entry>> {
+        entries(text.as_bytes(), file).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let entries = vec![
+            Entry::new("test.log", "plain").with_context("src/lib.rs:1"),
+            Entry::new("test.log", "with header").with_header("HEADER"),
+            Entry::new("test.log", "both")
+                .with_header("Header (with parens)")
+                .with_context("src/lib.rs:3"),
+            Entry::new("test.log", "multi\nline\n\ncontent").with_context("src/lib.rs:4"),
+            Entry::new("test.log", "fields")
+                .with_header("FIELDS")
+                .with_field("ident", "Foo")
+                .with_field("variants", 3),
+            Entry::new("test.log", "only fields").with_field("ok", true),
+        ];
+
+        let parsed = parse_str(&render_all(&entries), "test.log");
+        assert_eq!(parsed, entries);
+    }
+
+    #[test]
+    fn test_content_with_separator_lines() {
+        let tricky = format!("before\n\n{0}\nnot a header\n{0}\nafter", SEPARATOR_LINE);
+        let entries = vec![
+            Entry::new("test.log", tricky).with_header("FIRST"),
+            Entry::new("test.log", "second").with_header("SECOND"),
+        ];
+
+        let parsed = parse_str(&render_all(&entries), "test.log");
+        assert_eq!(parsed, entries);
+    }
+
+    #[test]
+    fn test_fenced_roundtrip() {
+        let tricky = format!(
+            "before\n\n{0}\n> looks like a header\n{0}\nafter",
+            SEPARATOR_LINE
+        );
+        let entries = vec![
+            Entry::new("test.log", tricky).with_header("FIRST"),
+            Entry::new("test.log", "no header or context"),
+            Entry::new("test.log", "fields").with_field("ratio", 1.0),
+            Entry::new("test.log", ">   key = value\nrest").with_field("ratio", 1.0),
+            Entry::new("test.log", ">\n>   key = value"),
+            Entry::new("test.log", "").with_context("src/lib.rs:3"),
+            Entry::new("test.log", "last")
+                .with_header("LAST")
+                .with_context("src/lib.rs:4"),
+        ];
+
+        let text: String = entries
+            .iter()
+            .map(|entry| Format::Framed.render(entry))
+            .collect();
+        assert_eq!(parse_str(&text, "test.log"), entries);
+    }
+
+    #[test]
+    fn test_unframed_entry_at_start() {
+        let entries = vec![
+            Entry::new("test.log", "unframed"),
+            Entry::new("test.log", "framed").with_header("HEADER"),
+        ];
+
+        let parsed = parse_str(&render_all(&entries), "test.log");
+        assert_eq!(parsed, entries);
+    }
+
+    fn fields_entry(file: &str, content: &str) -> Entry {
+        Entry::new(file, content)
+            .with_header("HEADER")
+            .with_context("src/lib.rs:7")
+            .with_field("count", 3)
+            .with_field("ratio", 1.5)
+            .with_field("ok", true)
+            .with_field("name", "two words")
+    }
+
+    #[test]
+    fn test_compact_roundtrip() {
+        let entries = vec![
+            fields_entry("test.log", "a | b\nc"),
+            Entry::new("test.log", "no header"),
+            Entry::new("test.log", "header only").with_header("HEADER"),
+            Entry::new("test.log", "a | b ] c")
+                .with_header("[nested] | header \\")
+                .with_context("src/a|b.rs:1")
+                .with_field("pipe", "x | y")
+                .with_field("bracket", "]"),
+        ];
+        let mut text: String = entries
+            .iter()
+            .map(|entry| Format::Compact.render(entry))
+            .collect();
+        assert_eq!(parse_str(&text, "test.log"), entries);
+
+        // compact lines may follow fenced entries
+        let framed = Entry::new("test.log", "framed").with_header("FRAMED");
+        text.insert_str(0, &Format::Framed.render(&framed));
+        let parsed = parse_str(&text, "test.log");
+        assert_eq!(parsed.len(), entries.len() + 1);
+        assert_eq!(parsed[0], framed);
+        assert_eq!(parsed[1..], entries[..]);
+    }
+
+    #[test]
+    fn test_markdown_roundtrip() {
+        let entries = vec![
+            fields_entry("test.md", "```\nnested fence\n```"),
+            Entry::new("test.md", "fn main() {}").with_kind(ContentKind::Rust),
+            Entry::new("test.md", "context only").with_context("src/lib.rs:8"),
+            Entry::new("test.md", "pipe").with_field("value", "a | b"),
+            Entry::new("test.md", "plain"),
+        ];
+        let text: String = entries
+            .iter()
+            .map(|entry| Format::Markdown.render(entry))
+            .collect();
+        assert_eq!(parse_str(&text, "test.md"), entries);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let entries = vec![
+            fields_entry("test.jsonl", "quote \" and \u{1f600}\n\ttab"),
+            Entry::new("test.jsonl", "plain"),
+        ];
+        let text: String = entries
+            .iter()
+            .map(|entry| Format::Json.render(entry))
+            .collect();
+        assert_eq!(parse_str(&text, "test.jsonl"), entries);
+
+        let error = entries_of("not json\n", "test.jsonl").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_log_files_include_jsonl() {
+        let dir = crate::test_dir("parse-log-files");
+        for file in ["a.log", "b.jsonl", "c.md", "report.html", "session.json"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let names: Vec<_> = log_files(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a.log", "b.jsonl", "c.md"]);
+    }
+}
@@ -0,0 +1,366 @@
+//! Generates a self-contained HTML report of the logs in a debug directory.
+//!
+//! The report is a single static file with one tab per log file,
+//! collapsible entries, a search box, source links built from the entry
+//! contexts and syntax highlighting for Rust content. Everything is inlined,
+//! so it can be opened straight from disk without a server or network.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! odebug::report::generate_html_report("target/odebug-report.html").unwrap();
+//! ```
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::entry::{ContentKind, Entry};
+use crate::{location, parse};
+
+/// Name of the report file written into the debug directory by default.
+pub const REPORT_FILE: &str = "report.html";
+
+/// Writes a report of every log in the [`debug_dir`](crate::debug_dir) to
+/// `output`.
+pub fn generate_html_report(output: impl AsRef<Path>) -> io::Result<()> {
+    Report::new().write_to(output)
+}
+
+/// Configuration of an HTML report.
+#[derive(Debug, Clone)]
+pub struct Report {
+    dir: PathBuf,
+    title: String,
+    source_base: String,
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        Report {
+            dir: location::debug_dir(),
+            title: "odebug report".to_string(),
+            source_base: format!("file://{}", cwd.display()),
+        }
+    }
+}
+
+impl Report {
+    /// Creates a report of the [`debug_dir`](crate::debug_dir), linking
+    /// sources relative to the current directory. Unlike `debug_dir`, this
+    /// does not create the directory, see [`find_location`](crate::find_location).
+    pub fn new() -> Self {
+        Report::default()
+    }
+
+    /// Sets the directory whose logs are included.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Sets the title of the report.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the prefix source links are built from. A context of
+    /// `src/lib.rs:42` links to `<base>/src/lib.rs#L42`, so a repository
+    /// URL such as `https://github.com/owner/repo/blob/main` works too.
+    pub fn source_base(mut self, base: impl Into<String>) -> Self {
+        self.source_base = base.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Reads the logs and renders the report.
+    pub fn render(&self) -> io::Result<String> {
+        let mut files = Vec::new();
+        for path in parse::log_files(&self.dir)? {
+            let name = path
+                .strip_prefix(&self.dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .into_owned();
+            let entries = parse::read_file(&path)?.collect::<io::Result<Vec<_>>>()?;
+            files.push((name, entries));
+        }
+        Ok(self.render_files(&files))
+    }
+
+    /// Reads the logs and writes the report to `output`.
+    pub fn write_to(&self, output: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(output, self.render()?)
+    }
+
+    fn render_files(&self, files: &[(String, Vec<Entry>)]) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n\
+             <header><h1>{0}</h1><p>{2}</p>\
+             <input id=\"search\" type=\"search\" placeholder=\"Search entries\" autofocus></header>\n\
+             <nav>",
+            escape(&self.title),
+            STYLE,
+            escape(&self.dir.display().to_string())
+        );
+        for (i, (name, entries)) in files.iter().enumerate() {
+            let _ = write!(
+                html,
+                "<button data-tab=\"{0}\"{1}>{2} <small>{3}</small></button>",
+                i,
+                if i == 0 { " class=\"active\"" } else { "" },
+                escape(name),
+                entries.len()
+            );
+        }
+        html.push_str("</nav>\n<main>\n");
+        if files.is_empty() {
+            html.push_str("<p class=\"empty\">No logs found.</p>\n");
+        }
+
+        for (i, (_, entries)) in files.iter().enumerate() {
+            let _ = writeln!(
+                html,
+                "<section id=\"tab-{0}\"{1}>",
+                i,
+                if i == 0 { "" } else { " hidden" }
+            );
+            for entry in entries {
+                self.render_entry(&mut html, entry);
+            }
+            html.push_str("</section>\n");
+        }
+
+        let _ = write!(
+            html,
+            "</main>\n<script>{0}</script>\n</body>\n</html>\n",
+            SCRIPT
+        );
+        html
+    }
+
+    fn render_entry(&self, html: &mut String, entry: &Entry) {
+        html.push_str("<details class=\"entry\" open><summary>");
+        match &entry.header {
+            Some(header) => {
+                let _ = write!(html, "<span class=\"header\">{}</span>", escape(header));
+            },
+            None => html.push_str("<span class=\"header none\">(no header)</span>"),
+        }
+        if let Some(context) = &entry.context {
+            let _ = write!(
+                html,
+                " <a class=\"source\" href=\"{0}\">{1}</a>",
+                escape(&self.source_link(context)),
+                escape(context)
+            );
+        }
+        html.push_str("</summary>");
+        if !entry.fields.is_empty() {
+            html.push_str("<table class=\"fields\">");
+            for (key, value) in &entry.fields {
+                let _ = write!(
+                    html,
+                    "<tr><th>{0}</th><td>{1}</td></tr>",
+                    escape(key),
+                    escape(&value.to_string())
+                );
+            }
+            html.push_str("</table>");
+        }
+        html.push_str("<pre><code>");
+        if entry.kind == ContentKind::Rust || looks_like_rust(&entry.content) {
+            highlight_rust(html, &entry.content);
+        } else {
+            html.push_str(&escape(&entry.content));
+        }
+        html.push_str("</code></pre></details>\n");
+    }
+
+    fn source_link(&self, context: &str) -> String {
+        match context.rsplit_once(':') {
+            Some((file, line)) if line.bytes().all(|b| b.is_ascii_digit()) => {
+                format!("{}/{}#L{}", self.source_base, file, line)
+            },
+            _ => format!("{}/{}", self.source_base, context),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Guesses whether content is Rust source, such as a token stream printed
+/// with `to_string()`.
+fn looks_like_rust(content: &str) -> bool {
+    let mut words = content.split(|c: char| !c.is_alphanumeric() && c != '_');
+    let has_item_keyword = words.any(|word| {
+        matches!(
+            word,
+            "fn" | "impl" | "struct" | "enum" | "trait" | "mod" | "pub" | "let"
+        )
+    });
+    has_item_keyword && content.contains(['{', ';', '(', ':'])
+}
+
+fn highlight_rust(html: &mut String, code: &str) {
+    let chars: Vec<char> = code.chars().collect();
+    let mut i = 0;
+    let span = |html: &mut String, class: &str, text: &[char]| {
+        let text: String = text.iter().collect();
+        let _ = write!(html, "<span class=\"{}\">{}</span>", class, escape(&text));
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            span(html, "cm", &chars[start..i]);
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            span(html, "st", &chars[start..i]);
+        } else if c == '\'' {
+            let char_literal = chars.get(i + 2) == Some(&'\'')
+                || (chars.get(i + 1) == Some(&'\\') && chars.get(i + 3) == Some(&'\''));
+            if char_literal {
+                i += if chars[i + 1] == '\\' { 4 } else { 3 };
+                span(html, "st", &chars[start..i]);
+            } else {
+                i += 1;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                span(html, "lt", &chars[start..i]);
+            }
+        } else if c.is_ascii_digit() {
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            span(html, "nu", &chars[start..i]);
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                span(html, "kw", &chars[start..i]);
+            } else if chars.get(i) == Some(&'!') {
+                i += 1;
+                span(html, "mc", &chars[start..i]);
+            } else if c.is_uppercase() {
+                span(html, "ty", &chars[start..i]);
+            } else {
+                html.push_str(&escape(&word));
+            }
+        } else {
+            i += 1;
+            html.push_str(&escape(&c.to_string()));
+        }
+    }
+}
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 0; background: #fafafa; color: #222; }
+header { padding: 1em 1.5em; background: #20232a; color: #eee; }
+header h1 { margin: 0; font-size: 1.3em; }
+header p { margin: .3em 0 .8em; font-family: monospace; color: #aaa; }
+#search { width: 100%; max-width: 40em; padding: .4em; font-size: 1em; }
+nav { display: flex; flex-wrap: wrap; gap: .3em; padding: .8em 1.5em; border-bottom: 1px solid #ddd; }
+nav button { border: 1px solid #ccc; background: #fff; padding: .3em .7em; cursor: pointer; border-radius: 3px; }
+nav button.active { background: #20232a; color: #fff; }
+main { padding: 1em 1.5em; }
+.entry { background: #fff; border: 1px solid #ddd; border-radius: 3px; margin-bottom: .6em; }
+.entry summary { padding: .4em .7em; cursor: pointer; }
+.entry .fields { border-top: 1px solid #eee; padding: .3em .7em; font-family: monospace; }
+.entry .fields th { text-align: left; color: #555; padding-right: 1em; font-weight: normal; }
+.entry pre { margin: 0; padding: .7em; border-top: 1px solid #eee; overflow-x: auto; white-space: pre-wrap; }
+.header { font-weight: bold; }
+.header.none { font-weight: normal; color: #999; }
+.source { margin-left: .5em; font-family: monospace; color: #07a; }
+.empty { color: #999; }
+.kw { color: #a626a4; } .st { color: #50a14f; } .cm { color: #a0a1a7; font-style: italic; }
+.nu { color: #986801; } .ty { color: #c18401; } .mc { color: #4078f2; } .lt { color: #e45649; }
+";
+
+const SCRIPT: &str = "
+const buttons = document.querySelectorAll('nav button');
+buttons.forEach(button => button.addEventListener('click', () => {
+  buttons.forEach(b => b.classList.toggle('active', b === button));
+  document.querySelectorAll('main section').forEach(section => {
+    section.hidden = section.id !== 'tab-' + button.dataset.tab;
+  });
+}));
+document.getElementById('search').addEventListener('input', event => {
+  const query = event.target.value.toLowerCase();
+  document.querySelectorAll('.entry').forEach(entry => {
+    entry.hidden = query !== '' && !entry.textContent.toLowerCase().includes(query);
+  });
+});
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    #[test]
+    fn test_report_contains_entries() {
+        let dir = crate::test_dir("report");
+        let entries = [
+            Entry::new("expand.log", "impl Foo { fn new() -> Self { Foo } }")
+                .with_header("Output")
+                .with_context("src/lib.rs:42"),
+            Entry::new("expand.log", "<escaped> & \"quoted\"").with_context("src/lib.rs:43"),
+        ];
+        let text: String = entries.iter().map(|e| Format::Text.render(e)).collect();
+        fs::write(dir.join("expand.log"), text).unwrap();
+
+        let html = Report::new()
+            .dir(&dir)
+            .source_base("https://example.com/repo/")
+            .render()
+            .unwrap();
+
+        assert!(html.contains("expand.log"));
+        assert!(html.contains("<span class=\"header\">Output</span>"));
+        assert!(html.contains("href=\"https://example.com/repo/src/lib.rs#L42\""));
+        assert!(html.contains("<span class=\"kw\">impl</span> <span class=\"ty\">Foo</span>"));
+        assert!(html.contains("&lt;escaped&gt; &amp; &quot;quoted&quot;"));
+        assert!(!html.contains("http://") && !html.contains("<script src"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+
+use crate::exit;
+use crate::format::json_string;
+
+/// Returns the name of the manifest describing the logging run of this
+/// process, `session.<pid>.json`, written next to the log files of every
+/// directory the file output writes to.
+///
+/// It records the start time, the process id, the cargo environment of the
+/// crate being compiled, the enabled odebug features and how the directory
+/// was chosen. When the process exits, it is rewritten with the end time
+/// and the number of entries written to each file. Each process has a
+/// manifest of its own, as parallel compiler processes often share a
+/// directory, and the manifests of processes that have exited are removed
+/// when a new run starts writing to it, where `/proc` tells which those are.
+pub fn session_file() -> String {
+    manifest_name(std::process::id())
+}
+
+fn manifest_name(pid: u32) -> String {
+    format!("session.{}.json", pid)
+}
+
+/// Environment variables cargo sets for the compiler, and so for
+/// proc-macros, recorded in the manifest if present.
+const ENV_VARS: &[&str] = &["CARGO_PKG_NAME", "CARGO_CRATE_NAME", "PROFILE", "TARGET"];
+
+const FEATURES: &[(&str, bool)] = &[
+    ("always_log", cfg!(feature = "always_log")),
+    ("cli", cfg!(feature = "cli")),
+    ("log", cfg!(feature = "log")),
+    ("output_to_target", cfg!(feature = "output_to_target")),
+    ("regex", cfg!(feature = "regex")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("use_workspace", cfg!(feature = "use_workspace")),
+];
+
+static STARTED: Lazy<SystemTime> = Lazy::new(SystemTime::now);
+static SESSIONS: Lazy<Mutex<Vec<Session>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// The run as seen from a single directory.
+struct Session {
+    pid: u32,
+    dir: PathBuf,
+    strategy: &'static str,
+    files: BTreeMap<String, u64>,
+}
+
+impl Session {
+    fn to_json(&self, finished: Option<SystemTime>) -> String {
+        let optional = |value: Option<String>| match value {
+            Some(value) => json_string(&value),
+            None => "null".to_string(),
+        };
+
+        let mut json = String::from("{\n");
+        let _ = writeln!(
+            json,
+            "  \"started\": {},",
+            json_string(&timestamp(*STARTED))
+        );
+        let _ = writeln!(
+            json,
+            "  \"finished\": {},",
+            optional(finished.map(timestamp))
+        );
+        let _ = writeln!(json, "  \"pid\": {},", self.pid);
+        json.push_str("  \"env\": {");
+        for (i, var) in ENV_VARS.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let value = optional(std::env::var(var).ok());
+            let _ = write!(json, "{}\n    {}: {}", separator, json_string(var), value);
+        }
+        json.push_str("\n  },\n");
+        let features: Vec<String> = FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| json_string(name))
+            .collect();
+        let _ = writeln!(json, "  \"features\": [{}],", features.join(", "));
+        let _ = writeln!(
+            json,
+            "  \"dir\": {},",
+            json_string(&self.dir.to_string_lossy())
+        );
+        let _ = writeln!(json, "  \"strategy\": {},", json_string(self.strategy));
+        json.push_str("  \"files\": {");
+        for (i, (file, count)) in self.files.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(json, "{}\n    {}: {}", separator, json_string(file), count);
+        }
+        if !self.files.is_empty() {
+            json.push_str("\n  ");
+        }
+        json.push_str("}\n}\n");
+        json
+    }
+
+    fn write(&self, finished: Option<SystemTime>) {
+        let _ = fs::write(
+            self.dir.join(manifest_name(self.pid)),
+            self.to_json(finished),
+        );
+    }
+}
+
+/// Counts an entry written to `file` in `dir`, writing the manifest of
+/// `dir` first if this is the first entry in it. `strategy` names how
+/// `dir` was chosen.
+pub(crate) fn count(dir: &Path, strategy: &'static str, file: &str) {
+    let Ok(mut sessions) = SESSIONS.lock() else {
+        return;
+    };
+    let index = match sessions.iter().position(|session| session.dir == dir) {
+        Some(index) => index,
+        None => {
+            if sessions.is_empty() {
+                exit::on_exit(finish);
+            }
+            remove_exited(dir);
+            let session = Session {
+                pid: std::process::id(),
+                dir: dir.to_path_buf(),
+                strategy,
+                files: BTreeMap::new(),
+            };
+            session.write(None);
+            sessions.push(session);
+            sessions.len() - 1
+        },
+    };
+    *sessions[index].files.entry(file.to_string()).or_default() += 1;
+}
+
+/// Removes the manifests of processes that have exited from `dir`.
+fn remove_exited(dir: &Path) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let name = entry.file_name();
+        let pid = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("session.")?.strip_suffix(".json"))
+            .and_then(|pid| pid.parse().ok());
+        if pid.is_some_and(has_exited) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Whether the process `pid` is known to have exited. Only `/proc` can
+/// tell, so elsewhere this is always false.
+pub(crate) fn has_exited(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    pid != std::process::id() && proc.join("self").exists() && !proc.join(pid.to_string()).exists()
+}
+
+/// Rewrites all manifests with the end time and entry counts.
+fn finish() {
+    let finished = SystemTime::now();
+    if let Ok(sessions) = SESSIONS.lock() {
+        for session in sessions.iter() {
+            session.write(Some(finished));
+        }
+    }
+}
+
+/// Formats `time` as an RFC 3339 timestamp in UTC, with milliseconds.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap_day = UNIX_EPOCH + Duration::from_millis(951_827_696_789);
+        assert_eq!(timestamp(leap_day), "2000-02-29T12:34:56.789Z");
+    }
+
+    #[test]
+    fn test_manifest_counts_entries() {
+        let dir = crate::test_dir("session");
+
+        count(&dir, "thread", "expand.log");
+        let manifest = fs::read_to_string(dir.join(session_file())).unwrap();
+        assert!(manifest.contains("\"finished\": null,"), "{}", manifest);
+        assert!(
+            manifest.contains("\"strategy\": \"thread\","),
+            "{}",
+            manifest
+        );
+        let pid = format!("\"pid\": {},", std::process::id());
+        assert!(manifest.contains(&pid), "{}", manifest);
+
+        count(&dir, "thread", "expand.log");
+        count(&dir, "thread", "parse/attrs.log");
+        finish();
+        let manifest = fs::read_to_string(dir.join(session_file())).unwrap();
+        assert!(manifest.contains("\"expand.log\": 2,"), "{}", manifest);
+        assert!(
+            manifest.contains("\"parse/attrs.log\": 1\n"),
+            "{}",
+            manifest
+        );
+        assert!(!manifest.contains("\"finished\": null"), "{}", manifest);
+    }
+
+    #[test]
+    fn test_parallel_sessions_keep_their_counts() {
+        let dir = crate::test_dir("session-parallel");
+        // no process has an id this large
+        fs::write(dir.join(manifest_name(4_000_000_000)), "{}").unwrap();
+
+        let mut sessions = Vec::new();
+        for (pid, count) in [(std::process::id(), 3), (1, 5)] {
+            let mut session = Session {
+                pid,
+                dir: dir.clone(),
+                strategy: "configured",
+                files: BTreeMap::new(),
+            };
+            session.files.insert("expand.log".to_string(), count);
+            session.write(Some(SystemTime::now()));
+            sessions.push(session);
+        }
+        count(&dir, "configured", "expand.log");
+        finish();
+
+        let manifest = |pid| fs::read_to_string(dir.join(manifest_name(pid))).unwrap();
+        assert!(manifest(1).contains("\"expand.log\": 5\n"));
+        assert!(manifest(1).contains("\"pid\": 1,"));
+        // the manifest of this process replaced the one written above
+        assert!(manifest(std::process::id()).contains("\"expand.log\": 1\n"));
+        if cfg!(target_os = "linux") {
+            assert!(!dir.join(manifest_name(4_000_000_000)).exists());
+        }
+    }
+}
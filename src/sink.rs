@@ -0,0 +1,646 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+
+use crate::entry::Entry;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::invocation::{self, INVOCATIONS_FILE};
+use crate::location::{self, debug_dir};
+use crate::name::validate_file_name;
+use crate::session;
+
+/// A destination for rendered log entries.
+///
+/// Every entry that passes the logger's filters is rendered once with the
+/// configured [`Format`](crate::Format) and then handed to each sink along
+/// with the structured [`Entry`] it was rendered from.
+pub trait Sink: Send + Sync {
+    /// Writes a single entry. `rendered` is the formatted text of `entry`.
+    fn write(&self, entry: &Entry, rendered: &str) -> io::Result<()>;
+
+    /// Flushes any buffered output.
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Controls what happens to an existing log file the first time it is
+/// written to by the current process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileMode {
+    /// Clear the file on the first write, so each run starts fresh
+    #[default]
+    Truncate,
+    /// Keep the previous contents and append to them
+    Append,
+}
+
+#[doc(hidden)]
+static INITIALIZED_FILES: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Writes entries to files in a debug directory, one file per
+/// [`Entry::file`].
+#[derive(Debug, Clone, Default)]
+pub struct FileSink {
+    dir: Option<PathBuf>,
+    mode: FileMode,
+}
+
+impl FileSink {
+    /// Creates a file sink writing to the [`debug_dir`] of the thread that
+    /// logs.
+    pub fn new() -> Self {
+        FileSink::default()
+    }
+
+    /// Creates a file sink writing to `dir` instead of the [`debug_dir`].
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        FileSink {
+            dir: Some(dir.into()),
+            mode: FileMode::default(),
+        }
+    }
+
+    /// Sets the [`FileMode`] of the sink.
+    pub fn mode(mut self, mode: FileMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the directory this sink writes to.
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(debug_dir)
+    }
+}
+
+impl Sink for FileSink {
+    fn write(&self, entry: &Entry, rendered: &str) -> io::Result<()> {
+        let current = match &self.dir {
+            Some(dir) => Some((dir.clone(), "logger")),
+            None => location::current(),
+        };
+        let Some((dir, strategy)) = current else {
+            // no debug directory is writable, see `Location::Stderr`
+            return io::stderr().write_all(rendered.as_bytes());
+        };
+        fs::create_dir_all(&dir).map_err(|e| directory_error(&dir, e))?;
+
+        let routed = invocation::route(&entry.file);
+        let file = validate_file_name(routed.as_deref().unwrap_or(&entry.file))
+            .map_err(Error::invalid_name)?;
+        let path = dir.join(&*file);
+        if file.contains('/') {
+            create_parent_dir(&dir, &path)?;
+        }
+
+        if routed.is_some() && self.mode == FileMode::Truncate {
+            invocation::clean_stale(&dir);
+        }
+        // the index is shared by parallel processes, see `invocation`
+        let should_clear = {
+            let mut initialized = INITIALIZED_FILES.lock().unwrap();
+            initialized.insert(path.clone())
+                && self.mode == FileMode::Truncate
+                && *file != *INVOCATIONS_FILE
+        };
+
+        if should_clear {
+            let _ = fs::remove_file(&path);
+        }
+
+        let failed = |operation| {
+            let path = &path;
+            move |e| io::Error::from(Error::from(e).with_operation(operation).with_path(path))
+        };
+        // buffered writer for better performance
+        let handle = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(failed(Operation::Open))?;
+        let mut writer = io::BufWriter::new(handle);
+        writer
+            .write_all(rendered.as_bytes())
+            .and_then(|()| writer.flush())
+            .map_err(failed(Operation::Write))?;
+
+        session::count(&dir, strategy, &file);
+        if let Some(routed) = routed {
+            invocation::count(&routed);
+        }
+        Ok(())
+    }
+}
+
+/// Creates the directories leading up to `path` one at a time, refusing to
+/// follow a symlink below `root`, so nothing is created outside of it.
+fn create_parent_dir(root: &Path, path: &Path) -> io::Result<()> {
+    let Some(relative) = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+    else {
+        return Ok(());
+    };
+    let mut dir = root.to_path_buf();
+    for component in relative.components() {
+        dir.push(component);
+        match fs::symlink_metadata(&dir) {
+            Ok(metadata) if metadata.is_dir() => continue,
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let outside = io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("symlink inside of {}", root.display()),
+                );
+                return Err(Error::new(ErrorKind::Permission)
+                    .with_operation(Operation::CreateDirectory)
+                    .with_path(&dir)
+                    .with_source(outside)
+                    .into());
+            },
+            Ok(_) => {
+                let not_dir = io::Error::new(io::ErrorKind::AlreadyExists, "not a directory");
+                return Err(directory_error(&dir, not_dir));
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => match fs::create_dir(&dir) {
+                // created by another thread in the meantime
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => {},
+                result => result.map_err(|e| directory_error(&dir, e))?,
+            },
+            Err(e) => return Err(directory_error(&dir, e)),
+        }
+    }
+    Ok(())
+}
+
+fn directory_error(dir: &Path, error: io::Error) -> io::Error {
+    Error::new(ErrorKind::Directory)
+        .with_operation(Operation::CreateDirectory)
+        .with_path(dir)
+        .with_source(error)
+        .into()
+}
+
+/// Writes rendered entries to the standard error stream.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrSink;
+
+impl StderrSink {
+    /// Creates a new stderr sink.
+    pub fn new() -> Self {
+        StderrSink
+    }
+}
+
+impl Sink for StderrSink {
+    fn write(&self, _entry: &Entry, rendered: &str) -> io::Result<()> {
+        io::stderr().lock().write_all(rendered.as_bytes())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Environment variable enabling the [`ConsoleSink`] of the default logger.
+///
+/// Set it to `1` or `all` to mirror every entry, or to a comma separated
+/// list of file names, e.g. `expand.log,parse.log`, to mirror only those.
+pub const MIRROR_ENV: &str = "ODEBUG_MIRROR";
+
+/// When a [`ConsoleSink`] uses ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Use colors if stderr is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    /// Always use colors
+    Always,
+    /// Never use colors
+    Never,
+}
+
+impl ColorMode {
+    fn enabled(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                use std::io::IsTerminal;
+                std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty())
+                    && io::stderr().is_terminal()
+            },
+        }
+    }
+}
+
+type Filter = Box<dyn Fn(&Entry) -> bool + Send + Sync>;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const HEADER: &str = "\x1b[1;36m";
+
+/// Mirrors entries to stderr for interactive runs, with the header, context
+/// and content set apart by ANSI colors.
+///
+/// Unlike the other sinks it renders entries itself, in a compact layout
+/// meant for the terminal, and has a filter of its own. It is not part of
+/// the default logger unless [`MIRROR_ENV`] is set, since output of
+/// proc-macros is swallowed by cargo anyway.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::sink::{ColorMode, ConsoleSink};
+/// use odebug::Logger;
+///
+/// let logger = Logger::builder()
+///     .sink(
+///         ConsoleSink::new()
+///             .color(ColorMode::Auto)
+///             .filter(|entry| entry.file == "expand.log"),
+///     )
+///     .build();
+/// ```
+pub struct ConsoleSink {
+    color: bool,
+    filter: Option<Filter>,
+}
+
+impl Default for ConsoleSink {
+    fn default() -> Self {
+        ConsoleSink {
+            color: ColorMode::Auto.enabled(),
+            filter: None,
+        }
+    }
+}
+
+impl ConsoleSink {
+    /// Creates a console sink mirroring every entry, colored according to
+    /// [`ColorMode::Auto`].
+    pub fn new() -> Self {
+        ConsoleSink::default()
+    }
+
+    /// Creates the console sink configured by [`MIRROR_ENV`], if any.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(MIRROR_ENV).ok()?;
+        match value.trim() {
+            "" | "0" | "false" => None,
+            "1" | "all" | "true" => Some(ConsoleSink::new()),
+            files => {
+                let files: Vec<String> = files
+                    .split(',')
+                    .map(|file| file.trim().trim_end_matches(".log").to_string())
+                    .collect();
+                Some(ConsoleSink::new().filter(move |entry| {
+                    let name = entry.file.trim_end_matches(".log");
+                    files.iter().any(|file| file == name)
+                }))
+            },
+        }
+    }
+
+    /// Sets the [`ColorMode`].
+    pub fn color(mut self, mode: ColorMode) -> Self {
+        self.color = mode.enabled();
+        self
+    }
+
+    /// Only mirrors entries accepted by `filter`, independently of what
+    /// the logger writes elsewhere.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Entry) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    fn write_entry<W: Write>(&self, entry: &Entry, out: &mut W) -> io::Result<()> {
+        let (reset, dim, header) = match self.color {
+            true => (RESET, DIM, HEADER),
+            false => ("", "", ""),
+        };
+
+        write!(out, "{0}[{1}]{2}", dim, entry.file, reset)?;
+        if let Some(text) = &entry.header {
+            write!(out, " {0}{1}{2}", header, text, reset)?;
+        }
+        if let Some(context) = &entry.context {
+            write!(out, " {0}{1}{2}", dim, context, reset)?;
+        }
+        writeln!(out)?;
+        for (key, value) in &entry.fields {
+            writeln!(out, "  {0}{1}{2} = {3}", dim, key, reset, value)?;
+        }
+        writeln!(out, "{0}", entry.content)
+    }
+}
+
+impl fmt::Debug for ConsoleSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsoleSink")
+            .field("color", &self.color)
+            .field("filtered", &self.filter.is_some())
+            .finish()
+    }
+}
+
+impl Sink for ConsoleSink {
+    fn write(&self, entry: &Entry, _rendered: &str) -> io::Result<()> {
+        if self.filter.as_ref().is_some_and(|filter| !filter(entry)) {
+            return Ok(());
+        }
+        self.write_entry(entry, &mut io::stderr().lock())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Keeps the most recent entries in memory, dropping the oldest ones once
+/// the capacity is reached.
+///
+/// The buffer is shared between clones, so one clone can be handed to the
+/// logger while another is kept around for reading.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::sink::RingBufferSink;
+/// use odebug::Logger;
+///
+/// let buffer = RingBufferSink::new(16);
+/// let logger = Logger::builder().file_output(false).sink(buffer.clone()).build();
+///
+/// logger.log("debug.log", None, "kept in memory").unwrap();
+/// assert_eq!(buffer.entries()[0].content, "kept in memory");
+/// ```
+#[derive(Clone)]
+pub struct RingBufferSink {
+    capacity: usize,
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+}
+
+impl RingBufferSink {
+    /// Creates a ring buffer holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        RingBufferSink {
+            capacity,
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Returns the maximum number of entries kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns a snapshot of the buffered entries, oldest first.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes and returns the buffered entries, oldest first.
+    pub fn drain(&self) -> Vec<Entry> {
+        self.entries.lock().unwrap().drain(..).collect()
+    }
+
+    /// Returns the number of buffered entries.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no entries are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all buffered entries.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for RingBufferSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingBufferSink")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl Sink for RingBufferSink {
+    fn write(&self, entry: &Entry, _rendered: &str) -> io::Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+        Ok(())
+    }
+}
+
+/// Streams rendered entries to a Unix domain socket, e.g. a live viewer
+/// listening with `nc -lU /tmp/odebug.sock`.
+///
+/// The connection is opened on the first write and reopened after a
+/// failure, so the listener may come and go while the process runs.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixSocketSink {
+    path: PathBuf,
+    stream: Mutex<Option<UnixStream>>,
+}
+
+#[cfg(unix)]
+impl UnixSocketSink {
+    /// Creates a sink connecting to the socket at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixSocketSink {
+            path: path.into(),
+            stream: Mutex::new(None),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Sink for UnixSocketSink {
+    fn write(&self, _entry: &Entry, rendered: &str) -> io::Result<()> {
+        let mut stream = self.stream.lock().unwrap();
+        if stream.is_none() {
+            *stream = Some(UnixStream::connect(&self.path)?);
+        }
+
+        let result = stream
+            .as_mut()
+            .map_or(Ok(()), |s| s.write_all(rendered.as_bytes()));
+        if result.is_err() {
+            // drop the broken connection so the next write reconnects
+            *stream = None;
+        }
+        result
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        match self.stream.lock().unwrap().as_mut() {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A sink backed by a user-supplied closure. Created with [`from_fn`].
+pub struct FnSink<F> {
+    f: F,
+}
+
+impl<F> fmt::Debug for FnSink<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnSink").finish_non_exhaustive()
+    }
+}
+
+impl<F> Sink for FnSink<F>
+where
+    F: Fn(&Entry, &str) -> io::Result<()> + Send + Sync,
+{
+    fn write(&self, entry: &Entry, rendered: &str) -> io::Result<()> {
+        (self.f)(entry, rendered)
+    }
+}
+
+/// Creates a sink that calls `f` with every entry and its rendered text.
+///
+/// # Examples
+///
+/// ```
+/// use odebug::{sink, Logger};
+///
+/// let logger = Logger::builder()
+///     .file_output(false)
+///     .sink(sink::from_fn(|entry, _rendered| {
+///         println!("{}: {}", entry.file, entry.content);
+///         Ok(())
+///     }))
+///     .build();
+///
+/// logger.log("debug.log", None, "handled by a closure").unwrap();
+/// ```
+pub fn from_fn<F>(f: F) -> FnSink<F>
+where
+    F: Fn(&Entry, &str) -> io::Result<()> + Send + Sync,
+{
+    FnSink {
+        f,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Logger;
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let buffer = RingBufferSink::new(2);
+        let logger = Logger::builder()
+            .file_output(false)
+            .sink(buffer.clone())
+            .build();
+
+        logger.log("debug.log", None, "first").unwrap();
+        logger.log("debug.log", None, "second").unwrap();
+        logger.log("debug.log", None, "third").unwrap();
+
+        let contents: Vec<_> = buffer.entries().into_iter().map(|e| e.content).collect();
+        assert_eq!(contents, ["second", "third"]);
+    }
+
+    #[test]
+    fn test_fn_sink_receives_rendered_entry() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_sink = Arc::clone(&seen);
+        let logger = Logger::builder()
+            .file_output(false)
+            .sink(from_fn(move |entry, rendered| {
+                seen_by_sink
+                    .lock()
+                    .unwrap()
+                    .push((entry.header.clone(), rendered.to_string()));
+                Ok(())
+            }))
+            .build();
+
+        logger.log("debug.log", Some("HEADER"), "content").unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].0.as_deref(), Some("HEADER"));
+        assert!(seen[0].1.contains("> HEADER"));
+        assert!(seen[0].1.contains("content"));
+    }
+
+    #[test]
+    fn test_console_sink_layout() {
+        let entry = Entry::new("expand.log", "content")
+            .with_header("Header")
+            .with_context("src/lib.rs:42");
+
+        let mut plain = Vec::new();
+        ConsoleSink::new()
+            .color(ColorMode::Never)
+            .write_entry(&entry, &mut plain)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(plain).unwrap(),
+            "[expand.log] Header src/lib.rs:42\ncontent\n"
+        );
+
+        let mut colored = Vec::new();
+        ConsoleSink::new()
+            .color(ColorMode::Always)
+            .write_entry(&entry, &mut colored)
+            .unwrap();
+        let colored = String::from_utf8(colored).unwrap();
+        assert!(colored.contains("\x1b[1;36mHeader\x1b[0m"), "{:?}", colored);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_sink() {
+        use std::io::Read;
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("odebug-sink-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let logger = Logger::builder()
+            .file_output(false)
+            .sink(UnixSocketSink::new(&path))
+            .build();
+        logger.log("debug.log", None, "over the socket").unwrap();
+        drop(logger);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert!(received.contains("over the socket"));
+
+        let _ = fs::remove_file(&path);
+    }
+}
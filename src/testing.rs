@@ -0,0 +1,318 @@
+//! Helpers for unit-testing code that logs through odebug.
+//!
+//! A [`Capture`] collects every entry logged on the current thread while it
+//! is alive, instead of writing them to the sinks. Since captures are
+//! thread-local, tests running in parallel never see each other's entries
+//! and never touch the filesystem.
+//!
+//! # Examples
+//!
+//! ```
+//! use odebug::{assert_logged, assert_logged_count, odebug};
+//!
+//! let capture = odebug::testing::capture();
+//!
+//! odebug!(expand::Found("struct {}", "Foo"));
+//!
+//! assert_logged_count!(capture, 1);
+//! assert_logged!(capture, "struct Foo");
+//! assert_logged!(capture, header = "Found", "Foo");
+//! assert_logged!(capture, file = "expand.log");
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::entry::Entry;
+
+type Entries = Rc<RefCell<Vec<Entry>>>;
+
+thread_local! {
+    static CAPTURES: RefCell<Vec<Entries>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Starts capturing the entries logged on the current thread.
+///
+/// Captures nest: while several are alive, entries go to the most recently
+/// started one.
+pub fn capture() -> Capture {
+    let entries = Entries::default();
+    CAPTURES.with(|captures| captures.borrow_mut().push(Rc::clone(&entries)));
+    Capture {
+        entries,
+    }
+}
+
+/// Hands `entry` to the innermost active capture of the current thread.
+/// Returns `false` if no capture is active.
+pub(crate) fn capture_entry(entry: &Entry) -> bool {
+    CAPTURES
+        .try_with(|captures| match captures.borrow().last() {
+            Some(entries) => {
+                entries.borrow_mut().push(entry.clone());
+                true
+            },
+            None => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Guard returned by [`capture()`]. Capturing stops when it is dropped.
+#[derive(Debug)]
+pub struct Capture {
+    entries: Entries,
+}
+
+impl Capture {
+    /// Returns a snapshot of the captured entries, oldest first.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.entries.borrow().clone()
+    }
+
+    /// Returns the number of captured entries.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    /// Returns `true` if nothing was captured.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all captured entries.
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    /// Returns `true` if any captured entry satisfies `predicate`.
+    pub fn any<F: Fn(&Entry) -> bool>(&self, predicate: F) -> bool {
+        self.entries.borrow().iter().any(predicate)
+    }
+
+    /// Returns the captured entries with the given header.
+    pub fn with_header(&self, header: &str) -> Vec<Entry> {
+        self.filtered(|entry| entry.header.as_deref() == Some(header))
+    }
+
+    /// Returns the captured entries destined for the given file.
+    pub fn in_file(&self, file: &str) -> Vec<Entry> {
+        self.filtered(|entry| entry.file == file)
+    }
+
+    /// Returns the captured entries with a field `key` whose value
+    /// displays as `value`.
+    pub fn with_field(&self, key: &str, value: &str) -> Vec<Entry> {
+        self.filtered(|entry| entry.field(key).is_some_and(|v| v.to_string() == value))
+    }
+
+    /// Returns `true` if the content of any captured entry contains `text`.
+    pub fn contains(&self, text: &str) -> bool {
+        self.any(|entry| entry.content.contains(text))
+    }
+
+    /// Returns `true` if the content of any captured entry matches the
+    /// regular expression `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn matches(&self, pattern: &str) -> bool {
+        let regex = regex::Regex::new(pattern)
+            .unwrap_or_else(|e| panic!("invalid regex `{}`: {}", pattern, e));
+        self.any(|entry| regex.is_match(&entry.content))
+    }
+
+    fn filtered<F: Fn(&Entry) -> bool>(&self, predicate: F) -> Vec<Entry> {
+        self.entries
+            .borrow()
+            .iter()
+            .filter(|entry| predicate(entry))
+            .cloned()
+            .collect()
+    }
+
+    #[doc(hidden)]
+    pub fn __describe(&self) -> String {
+        let entries = self.entries.borrow();
+        if entries.is_empty() {
+            return "no entries were captured".to_string();
+        }
+        let mut description = format!("{} captured entries:", entries.len());
+        for entry in entries.iter() {
+            description.push_str(&format!(
+                "\n  [{}] {}: {:?}",
+                entry.file,
+                entry.header.as_deref().unwrap_or("-"),
+                entry.content
+            ));
+        }
+        description
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = CAPTURES.try_with(|captures| {
+            captures
+                .borrow_mut()
+                .retain(|entries| !Rc::ptr_eq(entries, &self.entries));
+        });
+    }
+}
+
+/// Asserts that a [`Capture`] contains a matching entry.
+///
+/// Entries can be matched by content substring, by header or by file, and
+/// the latter two can be combined with a content substring.
+///
+/// ```
+/// use odebug::{assert_logged, odebug};
+///
+/// let capture = odebug::testing::capture();
+/// odebug!("custom.log" => "value = {}", 42);
+///
+/// assert_logged!(capture, "value = 42");
+/// assert_logged!(capture, file = "custom.log", "42");
+/// ```
+#[macro_export]
+macro_rules! assert_logged {
+    ($capture:expr, header = $header:expr $(, $text:expr)? $(,)?) => {{
+        let capture = &$capture;
+        let header: &str = &$header;
+        let text: Option<String> = None $(.or(Some($text.to_string())))?;
+        assert!(
+            capture.any(|entry| {
+                entry.header.as_deref() == Some(header)
+                    && text.as_deref().map_or(true, |text| entry.content.contains(text))
+            }),
+            "no entry with header {:?}{} was logged, {}",
+            header,
+            text.map(|text| format!(" containing {:?}", text)).unwrap_or_default(),
+            capture.__describe()
+        );
+    }};
+    ($capture:expr, file = $file:expr $(, $text:expr)? $(,)?) => {{
+        let capture = &$capture;
+        let file: &str = &$file;
+        let text: Option<String> = None $(.or(Some($text.to_string())))?;
+        assert!(
+            capture.any(|entry| {
+                entry.file == file
+                    && text.as_deref().map_or(true, |text| entry.content.contains(text))
+            }),
+            "no entry in file {:?}{} was logged, {}",
+            file,
+            text.map(|text| format!(" containing {:?}", text)).unwrap_or_default(),
+            capture.__describe()
+        );
+    }};
+    ($capture:expr, $text:expr $(,)?) => {{
+        let capture = &$capture;
+        let text: &str = &$text;
+        assert!(
+            capture.contains(text),
+            "no entry containing {:?} was logged, {}",
+            text,
+            capture.__describe()
+        );
+    }};
+}
+
+/// Asserts that a [`Capture`] holds exactly the given number of entries.
+#[macro_export]
+macro_rules! assert_logged_count {
+    ($capture:expr, $count:expr $(,)?) => {{
+        let capture = &$capture;
+        let expected: usize = $count;
+        assert!(
+            capture.len() == expected,
+            "expected {} logged entries, but {}",
+            expected,
+            capture.__describe()
+        );
+    }};
+}
+
+/// Asserts that the content of an entry in a [`Capture`] matches a regular
+/// expression. Requires the `regex` feature.
+#[cfg(feature = "regex")]
+#[macro_export]
+macro_rules! assert_logged_matches {
+    ($capture:expr, $pattern:expr $(,)?) => {{
+        let capture = &$capture;
+        let pattern: &str = &$pattern;
+        assert!(
+            capture.matches(pattern),
+            "no entry matching /{}/ was logged, {}",
+            pattern,
+            capture.__describe()
+        );
+    }};
+}
+
+#[cfg(all(test, any(debug_assertions, feature = "always_log")))]
+mod tests {
+    use super::*;
+    use crate::odebug;
+
+    #[test]
+    fn test_capture_collects_entries() {
+        let capture = capture();
+
+        odebug!(custom::Header("First {}", 1));
+        odebug!("Second");
+
+        assert_logged_count!(capture, 2);
+        assert_logged!(capture, "First 1");
+        assert_logged!(capture, header = "Header", "First");
+        assert_logged!(capture, file = "debug.log", "Second");
+        assert_eq!(capture.with_header("Header").len(), 1);
+        assert_eq!(capture.in_file("custom.log").len(), 1);
+    }
+
+    #[test]
+    fn test_nested_captures() {
+        let outer = capture();
+        odebug!("outer");
+        {
+            let inner = capture();
+            odebug!("inner");
+            assert_logged_count!(inner, 1);
+        }
+        odebug!("outer again");
+
+        assert_logged_count!(outer, 2);
+        assert!(!outer.contains("inner"));
+    }
+
+    #[test]
+    fn test_captures_are_thread_local() {
+        let capture = capture();
+        std::thread::spawn(|| {
+            let other = super::capture();
+            odebug!("other thread");
+            assert_logged_count!(other, 1);
+        })
+        .join()
+        .unwrap();
+
+        assert!(capture.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry containing")]
+    fn test_assert_logged_fails() {
+        let capture = capture();
+        odebug!("something else");
+        assert_logged!(capture, "missing");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_assert_logged_matches() {
+        let capture = capture();
+        odebug!("expanded {} variants", 3);
+        assert_logged_matches!(capture, r"expanded \d+ variants");
+    }
+}
@@ -0,0 +1,339 @@
+//! A [`tracing_subscriber::Layer`] writing spans and events into odebug
+//! files.
+//!
+//! Events become entries with the level and the current span scope as the
+//! header, e.g. `DEBUG expand{name=Foo}:derive`, the message as the content
+//! and the other fields of the event as the fields of the entry. Span
+//! enters and exits are logged as entries of their own. The file is chosen from the
+//! `odebug.file` field of the event if present, or from its target.
+//!
+//! # Examples
+//!
+//! ```
+//! use odebug::tracing_layer::OdebugLayer;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let subscriber = tracing_subscriber::registry().with(OdebugLayer::new());
+//! tracing::subscriber::with_default(subscriber, || {
+//!     let span = tracing::debug_span!("expand", name = "Foo");
+//!     let _enter = span.enter();
+//!     tracing::debug!(odebug.file = "expand.log", variants = 3, "expanded");
+//! });
+//! ```
+
+use std::fmt::{self, Write as _};
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::entry::{target_file, Entry, Value};
+
+/// The default name of the field routing an event to a file.
+pub const FILE_FIELD: &str = "odebug.file";
+
+/// A layer rendering tracing data through the global odebug
+/// [`logger`](crate::logger()).
+#[derive(Debug, Clone)]
+pub struct OdebugLayer {
+    file: Option<String>,
+    file_field: &'static str,
+    spans: bool,
+}
+
+impl Default for OdebugLayer {
+    fn default() -> Self {
+        OdebugLayer {
+            file: None,
+            file_field: FILE_FIELD,
+            spans: true,
+        }
+    }
+}
+
+impl OdebugLayer {
+    /// Creates a layer routing by target and logging span enters and exits.
+    pub fn new() -> Self {
+        OdebugLayer::default()
+    }
+
+    /// Writes everything without an explicit file field to `file` instead
+    /// of a per-target file.
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Sets the name of the field used to route events to a file.
+    /// Defaults to [`FILE_FIELD`].
+    pub fn file_field(mut self, name: &'static str) -> Self {
+        self.file_field = name;
+        self
+    }
+
+    /// Enables or disables logging of span enters and exits.
+    pub fn spans(mut self, enabled: bool) -> Self {
+        self.spans = enabled;
+        self
+    }
+
+    fn file_for(&self, metadata: &Metadata<'_>, routed: Option<String>) -> String {
+        routed
+            .or_else(|| self.file.clone())
+            .unwrap_or_else(|| target_file(metadata.target()))
+    }
+
+    fn log(&self, entry: Entry) {
+        let logger = crate::logger();
+        if let Err(e) = logger.log_entry(&entry) {
+            logger.handle_error(e);
+        }
+    }
+}
+
+/// The rendered fields of a span, kept in the span's extensions.
+struct SpanFields(String);
+
+impl<S> Layer<S> for OdebugLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::new(self.file_field);
+        attrs.record(&mut visitor);
+        span.extensions_mut()
+            .insert(SpanFields(visitor.fields_inline()));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::new(self.file_field);
+        values.record(&mut visitor);
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanFields>() {
+            Some(SpanFields(fields)) => {
+                if !fields.is_empty() && !visitor.fields.is_empty() {
+                    fields.push(' ');
+                }
+                fields.push_str(&visitor.fields_inline());
+            },
+            None => extensions.insert(SpanFields(visitor.fields_inline())),
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = FieldVisitor::new(self.file_field);
+        event.record(&mut visitor);
+
+        let mut header = metadata.level().to_string();
+        if let Some(scope) = ctx.event_scope(event) {
+            let spans: Vec<String> = scope.from_root().map(|span| span_label(&span)).collect();
+            if !spans.is_empty() {
+                header.push(' ');
+                header.push_str(&spans.join(":"));
+            }
+        }
+
+        let mut entry =
+            Entry::new(self.file_for(metadata, visitor.file), visitor.message).with_header(header);
+        for (name, value) in visitor.fields {
+            entry = entry.with_field(name, value);
+        }
+        if let Some(context) = location(metadata) {
+            entry = entry.with_context(context);
+        }
+        self.log(entry);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if self.spans {
+            self.log_span(id, "enter", ctx);
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if self.spans {
+            self.log_span(id, "exit", ctx);
+        }
+    }
+}
+
+impl OdebugLayer {
+    fn log_span<S>(&self, id: &Id, action: &str, ctx: Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let metadata = span.metadata();
+        let header = format!("{} {}", metadata.level(), span_label(&span));
+        let mut entry = Entry::new(self.file_for(metadata, None), format!("{} span", action))
+            .with_header(header);
+        if let Some(context) = location(metadata) {
+            entry = entry.with_context(context);
+        }
+        self.log(entry);
+    }
+}
+
+fn span_label<'a, R>(span: &tracing_subscriber::registry::SpanRef<'a, R>) -> String
+where
+    R: LookupSpan<'a>,
+{
+    match span.extensions().get::<SpanFields>() {
+        Some(SpanFields(fields)) if !fields.is_empty() => {
+            format!("{}{{{}}}", span.name(), fields)
+        },
+        _ => span.name().to_string(),
+    }
+}
+
+fn location(metadata: &Metadata<'_>) -> Option<String> {
+    match (metadata.file(), metadata.line()) {
+        (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+        _ => None,
+    }
+}
+
+struct FieldVisitor {
+    file_field: &'static str,
+    file: Option<String>,
+    message: String,
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl FieldVisitor {
+    fn new(file_field: &'static str) -> Self {
+        FieldVisitor {
+            file_field,
+            file: None,
+            message: String::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Records a field that is not a string, which includes `%value` and
+    /// `?value` fields.
+    fn record_value(&mut self, field: &Field, value: Value) {
+        if field.name() == self.file_field {
+            self.file = Some(value.to_string());
+        } else if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value));
+        }
+    }
+
+    fn fields_inline(&self) -> String {
+        let mut out = String::new();
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            let _ = write!(out, "{}={}", name, value);
+        }
+        out
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.file_field {
+            self.file = Some(value.to_string());
+        } else if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value.into()));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record_value(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record_value(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record_value(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record_value(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_value(field, Value::Str(format!("{:?}", value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_events_and_spans_become_entries() {
+        let capture = testing::capture();
+        let subscriber = tracing_subscriber::registry().with(OdebugLayer::new());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!(target: "expand", "derive", name = "Foo");
+            let _enter = span.enter();
+            tracing::debug!(target: "expand", variants = 3, "expanded");
+            tracing::info!(odebug.file = "routed.log", name = "Bar", "routed by field");
+        });
+
+        let entries = capture.entries();
+        assert_eq!(entries.len(), 4, "{}", capture.__describe());
+
+        assert_eq!(entries[0].file, "expand.log");
+        assert_eq!(entries[0].header.as_deref(), Some("DEBUG derive{name=Foo}"));
+        assert_eq!(entries[0].content, "enter span");
+
+        assert_eq!(entries[1].file, "expand.log");
+        assert_eq!(entries[1].header.as_deref(), Some("DEBUG derive{name=Foo}"));
+        assert_eq!(entries[1].content, "expanded");
+        assert_eq!(entries[1].field("variants"), Some(&Value::Int(3)));
+        assert!(entries[1].context.is_some());
+
+        assert_eq!(entries[2].file, "routed.log");
+        assert_eq!(entries[2].header.as_deref(), Some("INFO derive{name=Foo}"));
+        assert_eq!(entries[2].content, "routed by field");
+        assert_eq!(
+            entries[2].fields,
+            [("name".to_string(), Value::from("Bar"))]
+        );
+
+        assert_eq!(entries[3].content, "exit span");
+    }
+
+    #[test]
+    fn test_spans_can_be_disabled() {
+        let capture = testing::capture();
+        let layer = OdebugLayer::new().spans(false).file("all.log");
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _enter = tracing::info_span!("quiet").entered();
+            tracing::warn!("only event");
+        });
+
+        let entries = capture.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file, "all.log");
+        assert_eq!(entries[0].header.as_deref(), Some("WARN quiet"));
+    }
+}